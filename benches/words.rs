@@ -0,0 +1,64 @@
+//! Loading a single word with many readings and definitions, by the joined query that `Word::get` makes
+//! against the previous strategy of a query for the readings and then one for the definitions of each.
+//!
+//! Run with `cargo bench --bench words`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use otango::data;
+use r2d2_sqlite::SqliteConnectionManager;
+
+const WORD: &str = "単語";
+const READINGS: usize = 50;
+const DEFINITIONS: usize = 20;
+
+/// The readings of `word` as they were loaded before, with a query for the definitions of each reading
+fn per_reading(c: &data::Connection, word: &str) -> Vec<data::WordReading> {
+    let word_id: i64 = c.query_row("SELECT `rowid` FROM `word` WHERE `word` = ?1", [word], |row| row.get(0))
+        .unwrap();
+    let mut readings = c.prepare("SELECT `full`, `accent`, `rowid` FROM `word_reading` WHERE `word` = ?1")
+        .unwrap();
+    let readings = readings.query_map([word_id], |row| {
+        let mut definitions = c.prepare("SELECT `definition` FROM `definition` WHERE `definition`.`word_reading` = ?1")?;
+        let definitions = definitions.query_map([row.get::<_, i64>(2)?], |row| Ok(data::Definition { definition: row.get(0)? }))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(data::WordReading {
+            full: row.get(0)?,
+            accent: row.get(1)?,
+            definitions
+        })
+    }).unwrap();
+    readings.collect::<rusqlite::Result<_>>().unwrap()
+}
+
+fn words(criterion: &mut Criterion) {
+    let pool = r2d2::Pool::builder()
+        .max_size(1)
+        .build(SqliteConnectionManager::memory())
+        .unwrap();
+    let c = pool.get().unwrap();
+    data::migrate(&c).unwrap();
+    data::Word {
+        word: WORD.into(),
+        readings: (0..READINGS).map(|r| data::WordReading {
+            full: format!("reading{}", r),
+            accent: "0".into(),
+            definitions: (0..DEFINITIONS).map(|d| data::Definition { definition: format!("definition{}", d) }).collect()
+        }).collect(),
+        tags: vec![data::Tag { tag: "common".into() }],
+        revision: Default::default()
+    }.insert(&c).unwrap();
+
+    let word = data::Word::get(&c, WORD).unwrap().unwrap();
+    let definitions = |readings: &[data::WordReading]| readings.iter()
+        .map(|reading| reading.definitions.iter().map(ToString::to_string).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(definitions(&word.readings), definitions(&per_reading(&c, WORD)), "both strategies must load the same word");
+
+    let mut group = criterion.benchmark_group(format!("word of {} readings of {} definitions", READINGS, DEFINITIONS));
+    group.bench_function("joined", |b| b.iter(|| data::Word::get(&c, WORD).unwrap()));
+    group.bench_function("per reading", |b| b.iter(|| per_reading(&c, WORD)));
+    group.finish();
+}
+
+criterion_group!(benches, words);
+criterion_main!(benches);
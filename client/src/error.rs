@@ -0,0 +1,55 @@
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+#[derive(Debug)]
+pub enum Error {
+    /// The request must be made by a user, but the client has no identity or session
+    Anonymous,
+    Http(reqwest::Error),
+    Io(std::io::Error),
+    /// An error from the shared types, such as when signing
+    Otango(otango::error::Error),
+    Ssl(openssl::error::ErrorStack),
+    /// The server responded with an error status and message
+    Status(reqwest::StatusCode, String),
+    Url(url::ParseError)
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Anonymous => write!(f, "[Client Error] The request must be made by a user, but no identity was given"),
+            Self::Http(e) => write!(f, "[HTTP Error] {}", e),
+            Self::Io(e) => write!(f, "[IO Error] {}", e),
+            Self::Otango(e) => write!(f, "{}", e),
+            Self::Ssl(e) => write!(f, "[OpenSSL Error] {}", e),
+            Self::Status(status, message) => write!(f, "[Server Error] {}: {}", status, message),
+            Self::Url(e) => write!(f, "[URL Error] {}", e)
+        }
+    }
+}
+impl std::error::Error for Error {}
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Http(e)
+    }
+}
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+impl From<otango::error::Error> for Error {
+    fn from(e: otango::error::Error) -> Self {
+        Self::Otango(e)
+    }
+}
+impl From<openssl::error::ErrorStack> for Error {
+    fn from(e: openssl::error::ErrorStack) -> Self {
+        Self::Ssl(e)
+    }
+}
+impl From<url::ParseError> for Error {
+    fn from(e: url::ParseError) -> Self {
+        Self::Url(e)
+    }
+}
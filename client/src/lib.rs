@@ -0,0 +1,269 @@
+//! A client for the お単語 API.
+//!
+//! Requests made by a user are authorised by their session if they have logged in, otherwise each one is
+//! signed along with a challenge fetched for it, so the challenge and signing flow is handled here.
+
+use std::{collections::HashMap, path::Path};
+
+use openssl::pkey::{PKey, Private};
+use otango::data::{self, Algorithm, By, Signed};
+use reqwest::{header, Method, StatusCode, Url};
+use serde::{de::DeserializeOwned, Serialize};
+
+pub mod error;
+pub use error::{Error, Result};
+
+/// A user and the private key of their key pair
+pub struct Identity {
+    name: String,
+    key: PKey<Private>,
+    algorithm: Algorithm
+}
+impl Identity {
+    pub fn new(name: impl Into<String>, key: PKey<Private>, algorithm: Algorithm) -> Result<Self> {
+        algorithm.check_key(&key)?;
+        Ok(Self {
+            name: name.into(),
+            key,
+            algorithm
+        })
+    }
+    /// Use a PEM encoded private key
+    pub fn from_pem(name: impl Into<String>, pem: &[u8], algorithm: Algorithm) -> Result<Self> {
+        Self::new(name, PKey::private_key_from_pem(pem)?, algorithm)
+    }
+    /// Read a PEM encoded private key from a file
+    pub fn load(name: impl Into<String>, path: impl AsRef<Path>, algorithm: Algorithm) -> Result<Self> {
+        Self::from_pem(name, &std::fs::read(path)?, algorithm)
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    fn sign<T: Serialize>(&self, data: T) -> Result<Signed<T>> {
+        Ok(Signed::new(data, |bytes| self.algorithm.sign(&self.key, bytes))?)
+    }
+}
+
+pub struct Client {
+    http: reqwest::Client,
+    /// The root of the API, such as `https://example.com/v1/`
+    base: Url,
+    identity: Option<Identity>,
+    session: Option<data::Token>
+}
+impl Client {
+    /// A client of the server at `server`, such as `https://example.com`, or `https://example.com/otango`
+    /// when served under a path
+    pub fn new(server: &str) -> Result<Self> {
+        let mut server = Url::parse(server)?;
+        // A relative URL replaces the last segment of the path unless it ends with a slash
+        if !server.path().ends_with('/') {
+            let path = format!("{}/", server.path());
+            server.set_path(&path);
+        }
+        let base = server.join("v1/")?;
+        Ok(Self {
+            http: reqwest::Client::new(),
+            base,
+            identity: None,
+            session: None
+        })
+    }
+    /// Make requests as the user
+    pub fn with_identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+    fn identity(&self) -> Result<&Identity> {
+        self.identity.as_ref().ok_or(Error::Anonymous)
+    }
+    fn url(&self, path: &str) -> Result<Url> {
+        Ok(self.base.join(path)?)
+    }
+    /// The URL of an entry, such as `word/単語`, escaping the key so that it is a single segment
+    fn entry(&self, kind: &str, key: &str) -> Result<Url> {
+        let mut url = self.url(kind)?;
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.push(key);
+        }
+        Ok(url)
+    }
+
+    /// Register the identity with its public key
+    pub async fn register(&self, contact: Option<String>) -> Result<()> {
+        let identity = self.identity()?;
+        let certificate = data::Certificate {
+            name: identity.name.clone(),
+            contact,
+            pubkey: String::from_utf8_lossy(&identity.key.public_key_to_pem()?).into_owned(),
+            algorithm: identity.algorithm,
+            created: chrono::Utc::now()
+        };
+        let request = self.http.post(self.url("auth/register")?)
+            .json(&identity.sign(certificate)?);
+        check(request.send().await?).await?;
+        Ok(())
+    }
+    /// Get a challenge that can only authorise a request within the scope
+    pub async fn challenge(&self, scope: data::Scope) -> Result<String> {
+        let identity = self.identity()?;
+        let challenge = data::ChallengeRequest::Scoped {
+            user: identity.name.clone(),
+            scope
+        };
+        let request = self.http.post(self.url("auth/challenge")?)
+            .json(&identity.sign(challenge)?);
+        json(request).await
+    }
+    /// Sign `data` for a request to `url`, with a challenge fetched for it
+    async fn by<T: Serialize>(&self, method: Method, url: &Url, purpose: &str, data: T) -> Result<Signed<By<T>>> {
+        let identity = self.identity()?;
+        let challenge = self.challenge(data::Scope {
+            method: Some(method.to_string()),
+            // The server compares the path as sent, so it must be percent encoded
+            path: Some(url.path().to_string()),
+            purpose: Some(purpose.to_string())
+        }).await?;
+        identity.sign(By::new(identity.name.clone(), challenge, data))
+    }
+
+    /// Start a session, so that later requests need not be signed
+    pub async fn login(&mut self) -> Result<&data::Token> {
+        let url = self.url("auth/login")?;
+        let signed = self.by(Method::POST, &url, "login", ()).await?;
+        let token = json(self.http.post(url).json(&signed)).await?;
+        Ok(self.session.insert(token))
+    }
+    /// End the session, if there is one
+    pub async fn logout(&mut self) -> Result<()> {
+        if let Some(session) = self.session.take() {
+            let request = self.http.post(self.url("auth/logout")?)
+                .bearer_auth(&session.token);
+            check(request.send().await?).await?;
+        }
+        Ok(())
+    }
+    /// The sessions of the logged in user
+    pub async fn sessions(&self) -> Result<Vec<data::Session>> {
+        let session = self.session.as_ref().ok_or(Error::Anonymous)?;
+        json(self.http.get(self.url("auth/sessions")?).bearer_auth(&session.token)).await
+    }
+
+    pub async fn list_words(&self, order: data::WordOrder, tag: Option<&str>, after: Option<&str>, limit: Option<u32>) -> Result<data::WordPage> {
+        let mut url = self.url("word")?;
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("order", match order {
+                data::WordOrder::Reading => "reading",
+                data::WordOrder::Recent => "recent"
+            });
+            if let Some(tag) = tag {
+                query.append_pair("tag", tag);
+            }
+            if let Some(after) = after {
+                query.append_pair("after", after);
+            }
+            if let Some(limit) = limit {
+                query.append_pair("limit", &limit.to_string());
+            }
+        }
+        json(self.http.get(url)).await
+    }
+    pub async fn get_word(&self, word: &str) -> Result<Option<data::Word>> {
+        optional(self.http.get(self.entry("word", word)?)).await
+    }
+    /// Get the words that exist of those given
+    pub async fn get_words(&self, words: &[String]) -> Result<HashMap<String, data::Word>> {
+        json(self.http.post(self.url("word/batch")?).json(words)).await
+    }
+    /// Add a word, which requires the user to be an administrator
+    pub async fn set_word(&self, word: &data::Word) -> Result<()> {
+        let url = self.entry("word", &word.word)?;
+        let request = match &self.session {
+            Some(session) => self.http.post(url)
+                .bearer_auth(&session.token)
+                .json(word),
+            None => {
+                let signed = self.by(Method::POST, &url, "set_word", word).await?;
+                self.http.post(url).json(&signed)
+            }
+        };
+        check(request.send().await?).await?;
+        Ok(())
+    }
+    /// Replace the readings and tags of a word, which requires the user to be an administrator.
+    ///
+    /// Returns false if there is no such word.
+    pub async fn update_word(&self, word: &data::Word) -> Result<bool> {
+        let url = self.entry("word", &word.word)?;
+        let request = match &self.session {
+            Some(session) => self.http.put(url)
+                .bearer_auth(&session.token)
+                .json(word),
+            None => {
+                let signed = self.by(Method::PUT, &url, "update_word", word).await?;
+                self.http.put(url).json(&signed)
+            }
+        };
+        let response = request.send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false)
+        }
+        check(response).await?;
+        Ok(true)
+    }
+    pub async fn get_kanji(&self, kanji: char) -> Result<Option<data::Kanji>> {
+        optional(self.http.get(self.entry("kanji", &kanji.to_string())?)).await
+    }
+    /// Get the kanji that exist of those given
+    pub async fn get_kanji_batch(&self, kanji: &[char]) -> Result<HashMap<char, data::Kanji>> {
+        json(self.http.post(self.url("kanji/batch")?).json(kanji)).await
+    }
+}
+
+/// Turn an error status into an error with the message the server gave
+async fn check(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        Ok(response)
+    } else {
+        Err(Error::Status(status, response.text().await.unwrap_or_default()))
+    }
+}
+async fn json<T: DeserializeOwned>(request: reqwest::RequestBuilder) -> Result<T> {
+    let response = request
+        .header(header::ACCEPT, "application/json")
+        .send()
+        .await?;
+    Ok(check(response).await?.json().await?)
+}
+/// As `json`, but `None` if there is no such entry
+async fn optional<T: DeserializeOwned>(request: reqwest::RequestBuilder) -> Result<Option<T>> {
+    let response = request
+        .header(header::ACCEPT, "application/json")
+        .send()
+        .await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None)
+    }
+    Ok(Some(check(response).await?.json().await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_keeps_the_path_prefix() {
+        for (server, base) in [
+            ("https://example.com", "https://example.com/v1/"),
+            ("https://example.com/", "https://example.com/v1/"),
+            ("https://example.com/otango", "https://example.com/otango/v1/"),
+            ("https://example.com/otango/", "https://example.com/otango/v1/")
+        ] {
+            assert_eq!(Client::new(server).unwrap().base.as_str(), base);
+        }
+        let client = Client::new("https://example.com/otango").unwrap();
+        assert_eq!(client.entry("word", "単語").unwrap().path(), "/otango/v1/word/%E5%8D%98%E8%AA%9E");
+    }
+}
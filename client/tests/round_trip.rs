@@ -0,0 +1,77 @@
+//! Requests made by the client against a server on a local port
+
+use actix_web::{web::Data, App, HttpServer};
+use openssl::{ec::{EcGroup, EcKey}, nid::Nid, pkey::PKey};
+use otango::{cache, data, web};
+use otango_client::{Client, Error, Identity};
+use r2d2_sqlite::SqliteConnectionManager;
+
+type Pool = r2d2::Pool<SqliteConnectionManager>;
+
+/// Serve the API from an empty in-memory database, returning the address of the server
+fn serve(pool: Pool) -> String {
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(cache::Dictionary::new(16, std::time::Duration::from_secs(60))))
+            .app_data(Data::new(web::limit::Limiter::<String>::new(0, 0)))
+            .configure(web::api)
+    }).workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+    let address = server.addrs()[0];
+    actix_web::rt::spawn(server.run());
+    format!("http://{}", address)
+}
+
+#[actix_web::test]
+async fn signed_requests_round_trip() {
+    // Every connection would open a separate database
+    let pool = r2d2::Pool::builder()
+        .max_size(1)
+        .build(SqliteConnectionManager::memory())
+        .unwrap();
+    data::migrate(&pool.get().unwrap()).unwrap();
+    let server = serve(pool.clone());
+
+    let key = PKey::from_ec_key(EcKey::generate(&EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap()).unwrap()).unwrap();
+    let identity = Identity::new("alice", key, data::Algorithm::EcdsaP256Sha256).unwrap();
+    let client = Client::new(&server).unwrap().with_identity(identity);
+    client.register(None).await.unwrap();
+
+    let word = data::Word {
+        word: "単語".into(),
+        readings: Vec::new(),
+        tags: vec![data::Tag { tag: "common".into() }],
+        revision: Default::default()
+    };
+    // Only administrators may add words
+    match client.set_word(&word).await {
+        Err(Error::Status(status, _)) => assert_eq!(status, 403),
+        result => panic!("a user without privilege set a word: {:?}", result.err())
+    }
+    data::User::set_privilege(&pool.get().unwrap(), "alice", data::Privilege::Admin).unwrap();
+    client.set_word(&word).await.unwrap();
+    match client.set_word(&word).await {
+        Err(Error::Status(status, _)) => assert_eq!(status, 409),
+        result => panic!("a word was added twice: {:?}", result.err())
+    }
+
+    let fetched = client.get_word("単語").await.unwrap().expect("the word was not added");
+    assert_eq!(fetched.tags.len(), 1);
+    let updated = data::Word {
+        readings: vec![data::WordReading {
+            full: "たんご".into(),
+            accent: "0".into(),
+            definitions: vec![data::Definition { definition: "word".into() }]
+        }],
+        ..word
+    };
+    assert!(client.update_word(&updated).await.unwrap());
+    let fetched = client.get_word("単語").await.unwrap().unwrap();
+    assert_eq!((fetched.readings.len(), fetched.tags.len()), (1, 1));
+    assert!(!client.update_word(&data::Word { word: "言葉".into(), ..updated }).await.unwrap());
+    assert!(client.get_word("言葉").await.unwrap().is_none());
+    let page = client.list_words(data::WordOrder::Recent, Some("common"), None, None).await.unwrap();
+    assert_eq!(page.words.len(), 1);
+}
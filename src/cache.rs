@@ -0,0 +1,188 @@
+use std::{
+    hash::Hash,
+    sync::{
+        Arc,
+        Mutex,
+        atomic::{AtomicU64, Ordering}
+    },
+    time::{Duration, Instant}
+};
+
+use lru::LruCache;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::data::{Kanji, Word};
+
+/// Caches of the dictionary entries that are read most often
+pub struct Dictionary {
+    pub words: Cache<String, Word>,
+    pub kanji: Cache<char, Kanji>
+}
+impl Dictionary {
+    /// Keep up to `capacity` words, and separately kanji, for at most `ttl` each
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            words: Cache::new(capacity, ttl),
+            kanji: Cache::new(capacity, ttl)
+        }
+    }
+    /// Forget every cached entry. Must be called after the server writes to the dictionary.
+    /// Writes made by anything else, such as `otango seed`, are only seen once the entries expire.
+    pub fn invalidate(&self) {
+        self.words.invalidate();
+        self.kanji.invalidate();
+    }
+    pub fn stats(&self) -> DictionaryStats {
+        DictionaryStats {
+            words: self.words.stats(),
+            kanji: self.kanji.stats()
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DictionaryStats {
+    pub words: CacheStats,
+    pub kanji: CacheStats
+}
+
+/// A bounded least-recently-used cache, with entries that expire, that counts hits and misses
+pub struct Cache<K: Hash + Eq, V> {
+    /// Each value along with when it was cached
+    entries: Mutex<LruCache<K, (Instant, Arc<V>)>>,
+    /// How long a value is served from the cache before it must be loaded again
+    ttl: Duration,
+    /// Incremented on every invalidation so that values loaded before it are not cached
+    generation: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64
+}
+impl<K: Hash + Eq, V> Cache<K, V> {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(capacity.max(1))),
+            ttl,
+            generation: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0)
+        }
+    }
+    /// The current generation, to be taken before loading a value to later `insert`
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+    pub fn get(&self, key: &K) -> Option<Arc<V>> {
+        self.get_at(key, Instant::now())
+    }
+    fn get_at(&self, key: &K, now: Instant) -> Option<Arc<V>> {
+        let mut entries = self.entries.lock().unwrap();
+        let value = match entries.get(key) {
+            Some((cached, value)) if now.duration_since(*cached) < self.ttl => Some(value.clone()),
+            Some(_) => {
+                entries.pop(key);
+                None
+            },
+            None => None
+        };
+        if value.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        value
+    }
+    /// Cache a value loaded during `generation`.
+    ///
+    /// The value is not cached if the cache was invalidated since, as it may be stale.
+    pub fn insert(&self, generation: u64, key: K, value: V) -> Arc<V> {
+        self.insert_at(generation, key, value, Instant::now())
+    }
+    fn insert_at(&self, generation: u64, key: K, value: V, now: Instant) -> Arc<V> {
+        let value = Arc::new(value);
+        let mut entries = self.entries.lock().unwrap();
+        if self.generation() == generation {
+            entries.put(key, (now, value.clone()));
+        }
+        value
+    }
+    pub fn invalidate(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        entries.clear();
+    }
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.entries.lock().unwrap();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: entries.len(),
+            capacity: entries.cap()
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub capacity: usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn value_loaded_before_invalidation_is_not_cached() {
+        let cache = Cache::new(4, Duration::from_secs(60));
+        let generation = cache.generation();
+        // A write and invalidation happen while the value is being loaded
+        cache.invalidate();
+        let value = cache.insert(generation, "word", 1);
+        assert_eq!(*value, 1, "the loaded value is still returned");
+        assert!(cache.get(&"word").is_none());
+
+        let generation = cache.generation();
+        cache.insert(generation, "word", 2);
+        assert_eq!(cache.get(&"word").as_deref(), Some(&2));
+    }
+
+    #[test]
+    fn invalidation_clears_cached_values() {
+        let cache = Cache::new(4, Duration::from_secs(60));
+        cache.insert(cache.generation(), 'a', "before");
+        cache.invalidate();
+        assert!(cache.get(&'a').is_none());
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (0, 1, 0));
+    }
+
+    #[test]
+    fn least_recently_used_is_evicted() {
+        let cache = Cache::new(2, Duration::from_secs(60));
+        for key in 0..3 {
+            cache.insert(cache.generation(), key, key);
+            cache.get(&0);
+        }
+        assert!(cache.get(&0).is_some());
+        assert!(cache.get(&1).is_none());
+        assert!(cache.get(&2).is_some());
+    }
+
+    #[test]
+    fn values_expire_after_the_ttl() {
+        let cache = Cache::new(4, Duration::from_secs(60));
+        let start = Instant::now();
+        cache.insert_at(cache.generation(), "word", 1, start);
+        assert!(cache.get_at(&"word", start + Duration::from_secs(59)).is_some());
+        // A write made elsewhere is seen once the entry expires
+        assert!(cache.get_at(&"word", start + Duration::from_secs(60)).is_none());
+        assert_eq!(cache.stats().entries, 0);
+
+        let cache = Cache::new(4, Duration::ZERO);
+        cache.insert(cache.generation(), "word", 1);
+        assert!(cache.get(&"word").is_none(), "a ttl of 0 caches nothing");
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{prelude::*, Config};
+
+use data::Privilege;
+
+pub const USAGE: &str = "\
+Usage: otango [COMMAND]
+
+Commands:
+    serve                   Serve the API (the default)
+    migrate                 Create the database schema, or upgrade it to the latest version
+    seed                    Insert the test data into the database
+    check-config            Check that the configuration can be served
+    --print-config          Print the configuration as environment variables
+    user list               List the registered users
+    user promote <NAME>     Make a user an administrator
+    user demote <NAME>      Revoke the administrator privilege of a user
+    user delete <NAME>      Delete a user along with their challenges and sessions
+    help                    Print this message";
+
+pub enum Command {
+    Serve,
+    Migrate,
+    Seed,
+    CheckConfig,
+    PrintConfig,
+    User(UserCommand),
+    Help
+}
+pub enum UserCommand {
+    List,
+    Promote(String),
+    Demote(String),
+    Delete(String)
+}
+impl Command {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> std::result::Result<Self, String> {
+        let command = match args.next().as_deref() {
+            None | Some("serve") => Self::Serve,
+            Some("migrate") => Self::Migrate,
+            Some("seed") => Self::Seed,
+            Some("check-config") => Self::CheckConfig,
+            Some("--print-config") => Self::PrintConfig,
+            Some("help" | "-h" | "--help") => Self::Help,
+            Some("user") => {
+                let command = args.next();
+                let mut name = || args.next().ok_or_else(|| "Missing the name of the user".to_string());
+                Self::User(match command.as_deref() {
+                    Some("list") => UserCommand::List,
+                    Some("promote") => UserCommand::Promote(name()?),
+                    Some("demote") => UserCommand::Demote(name()?),
+                    Some("delete") => UserCommand::Delete(name()?),
+                    Some(command) => return Err(format!("Unknown user command {:?}", command)),
+                    None => return Err("Missing a user command".to_string())
+                })
+            },
+            Some(command) => return Err(format!("Unknown command {:?}", command))
+        };
+        if let Some(arg) = args.next() {
+            return Err(format!("Unexpected argument {:?}", arg))
+        }
+        Ok(command)
+    }
+    /// Whether the command must not run with an invalid configuration.
+    /// Administration commands only need the database, so they are allowed to run.
+    pub fn validates(&self) -> bool {
+        matches!(self, Self::Serve | Self::CheckConfig)
+    }
+}
+
+/// Run any command but `serve`
+pub(crate) fn run(command: Command, config: &'static Config) -> Result<()> {
+    match command {
+        Command::Serve => unreachable!("Serving is asynchronous"),
+        Command::Help => println!("{}", USAGE),
+        // Printed before validation so that an invalid configuration can be inspected
+        Command::PrintConfig => (),
+        Command::CheckConfig => check_config(config)?,
+        Command::Migrate => migrate(&connect(config)?)?,
+        Command::Seed => {
+            let c = connect(config)?;
+            require_schema(&c)?;
+            c.execute_batch(include_str!("test_data.sql"))?;
+            println!("Inserted the test data");
+        },
+        Command::User(command) => {
+            let c = connect(config)?;
+            require_schema(&c)?;
+            match command {
+                UserCommand::List => for user in data::User::list(&c)? {
+                    println!(
+                        "{}\t{:?}\t{}\t{}",
+                        user.name,
+                        user.privilege,
+                        user.algorithm.name(),
+                        user.contact.as_deref().unwrap_or("-")
+                    );
+                },
+                UserCommand::Promote(name) => {
+                    data::User::set_privilege(&c, &name, Privilege::Admin)?;
+                    println!("{} is now an administrator", name);
+                },
+                UserCommand::Demote(name) => {
+                    data::User::set_privilege(&c, &name, Privilege::None)?;
+                    println!("{} is no longer an administrator", name);
+                },
+                UserCommand::Delete(name) => {
+                    data::User::delete(&c, &name)?;
+                    println!("Deleted {}", name);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn connect(config: &'static Config) -> Result<data::Connection> {
+    Ok(config.pool(1)?.get()?)
+}
+
+/// Create or upgrade the schema
+fn migrate(c: &data::Connection) -> Result<()> {
+    match data::migrate(c)? {
+        data::Migration::Current(version) => println!("The schema is up to date at version {}", version),
+        data::Migration::Created(version) => println!("Created the schema at version {}", version),
+        data::Migration::Upgraded { from, to, violations } => {
+            println!("Upgraded the schema from version {} to {}", from, to);
+            if violations > 0 {
+                println!("{} rows reference rows that do not exist, see `PRAGMA foreign_key_check`", violations);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn require_schema(c: &data::Connection) -> Result<()> {
+    let version = data::schema_version(c)?;
+    if version != data::SCHEMA_VERSION {
+        return Err(Error::Other(format!(
+            "The database has schema version {}, expected {}. Run `otango migrate` first.",
+            version,
+            data::SCHEMA_VERSION
+        ).into()))
+    }
+    Ok(())
+}
+
+/// The configuration has already been validated, so check what can only be known by using it
+fn check_config(config: &'static Config) -> Result<()> {
+    if config.tls {
+        crate::tls::Reloadable::load(&config.key, &config.cert)?;
+        let expiry = crate::tls::expiry(&config.cert)?;
+        println!("The certificate expires {}, in {} days", expiry.not_after, expiry.days);
+    }
+    let c = connect(config)?;
+    let version = data::schema_version(&c)?;
+    println!("The database has schema version {}, expected {}", version, data::SCHEMA_VERSION);
+    println!("The configuration is valid");
+    Ok(())
+}
@@ -1,12 +1,22 @@
 use super::prelude::*;
 
+use serde_json::value::RawValue;
+use openssl::{
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{HasPublic, Id, PKey, PKeyRef, Private},
+    rsa::Padding,
+    sign::{RsaPssSaltlen, Signer, Verifier}
+};
+
 #[derive(Serialize, Clone, Deserialize)]
 pub struct User {
     pub name: String,
     pub contact: Option<String>,
     pub image: Option<Vec<u8>>,
     pub privilege: Privilege,
-    pub pubkey: String
+    pub pubkey: String,
+    pub algorithm: Algorithm
 }
 impl User {
     pub fn pubkey(c: &Connection, name: &str) -> Result<Option<String>> {
@@ -14,43 +24,79 @@ impl User {
         Ok(s.query_row([name], |row| row.get(0)).optional()?)
     }
     pub fn named(c: &Connection, name: &str) -> Result<Option<Self>> {
-        let mut s = c.prepare("SELECT `name`, `contact`, `image`, `privilege`, `pubkey` FROM `user` WHERE `name` = ?1")?;
-        Ok(s.query_row([name], |row| Ok(Self {
+        let mut s = c.prepare("SELECT `name`, `contact`, `image`, `privilege`, `pubkey`, `algorithm` FROM `user` WHERE `name` = ?1")?;
+        Ok(s.query_row([name], Self::from_row).optional()?)
+    }
+    /// Every user, ordered by name
+    pub fn list(c: &Connection) -> Result<Vec<Self>> {
+        let mut s = c.prepare("SELECT `name`, `contact`, `image`, `privilege`, `pubkey`, `algorithm` FROM `user` ORDER BY `name`")?;
+        let users = s.query_map([], Self::from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(users)
+    }
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Self {
             name: row.get(0)?,
             contact: row.get(1)?,
             image: row.get(2)?,
             privilege: row.get(3)?,
             pubkey: row.get(4)?,
-        })).optional()?)
+            algorithm: Algorithm::stored(row.get(5)?, &row.get::<_, String>(4)?),
+        })
     }
+    #[tracing::instrument(level = "debug", skip(self, c), fields(user = %self.name))]
     pub fn insert(&self, c: &Connection) -> Result<()> {
-        let mut s = c.prepare("INSERT OR IGNORE INTO `user` (`name`, `contact`, `image`, `privilege`, `pubkey`) VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        let mut s = c.prepare("INSERT OR IGNORE INTO `user` (`name`, `contact`, `image`, `privilege`, `pubkey`, `algorithm`) VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?;
         if s.execute(rusqlite::params!(
             &self.name,
             &self.contact,
             &self.image,
             &self.privilege,
-            &self.pubkey
+            &self.pubkey,
+            &self.algorithm
         ))? != 1 {
             Err(Error::Exists(self.name.clone()))
         } else {
             Ok(())
         }
     }
-    /// Get the public key and privilege of the user
-    pub fn credentials(c: &Connection, name: &str) -> Result<Option<(String, Privilege)>> {
-        c.prepare("SELECT `pubkey`, `privilege` FROM `user` WHERE `name` = ?1")?
+    #[tracing::instrument(level = "debug", skip(c))]
+    pub fn set_privilege(c: &Connection, name: &str, privilege: Privilege) -> Result<()> {
+        let mut s = c.prepare("UPDATE `user` SET `privilege` = ?2 WHERE `name` = ?1")?;
+        if s.execute(rusqlite::params!(name, privilege))? != 1 {
+            Err(Error::NoUser(name.to_string()))
+        } else {
+            Ok(())
+        }
+    }
+    /// Delete the user along with their challenges and sessions
+    #[tracing::instrument(level = "debug", skip(c))]
+    pub fn delete(c: &Connection, name: &str) -> Result<()> {
+        let transaction = c.unchecked_transaction()?;
+        transaction.execute("DELETE FROM `challenge` WHERE `user` = (SELECT `rowid` FROM `user` WHERE `name` = ?1)", [name])?;
+        transaction.execute("DELETE FROM `session` WHERE `user` = (SELECT `rowid` FROM `user` WHERE `name` = ?1)", [name])?;
+        if transaction.execute("DELETE FROM `user` WHERE `name` = ?1", [name])? != 1 {
+            return Err(Error::NoUser(name.to_string()))
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+    /// Get the public key, signature algorithm and privilege of the user
+    #[tracing::instrument(level = "debug", skip(c))]
+    pub fn credentials(c: &Connection, name: &str) -> Result<Option<(String, Algorithm, Privilege)>> {
+        c.prepare("SELECT `pubkey`, `algorithm`, `privilege` FROM `user` WHERE `name` = ?1")?
             .query_row([name], |row| {
-                let pubkey = row.get(0)?;
-                let privilege = row.get(1)?;
-                Ok((pubkey, privilege))
+                let pubkey: String = row.get(0)?;
+                let algorithm = Algorithm::stored(row.get(1)?, &pubkey);
+                let privilege = row.get(2)?;
+                Ok((pubkey, algorithm, privilege))
             })
             .optional()
             .map_err(|e| e.into())
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum Privilege {
     None,
     Admin
@@ -62,11 +108,11 @@ impl std::cmp::PartialOrd for Privilege {
         this.partial_cmp(&other)
     }
 }
-impl Into<u8> for &Privilege {
-    fn into(self) -> u8 {
-        match self {
-            &Privilege::None => 0,
-            &Privilege::Admin => 1
+impl From<&Privilege> for u8 {
+    fn from(privilege: &Privilege) -> u8 {
+        match privilege {
+            Privilege::None => 0,
+            Privilege::Admin => 1
         }
     }
 }
@@ -93,23 +139,211 @@ impl rusqlite::types::FromSql for Privilege {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+/// A signature scheme that a user's key pair is used with.
+/// 
+/// Names follow JSON Web Algorithms. ECDSA signatures are DER encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum Algorithm {
+    /// RSASSA-PKCS1-v1_5 with SHA-256, the default for RSA keys registered before algorithms were declared
+    #[serde(rename = "RS256")]
+    RsaPkcs1Sha256,
+    /// RSASSA-PSS with SHA-256 and a salt the length of the digest
+    #[serde(rename = "PS256")]
+    RsaPssSha256,
+    /// ECDSA over P-256 with SHA-256
+    #[serde(rename = "ES256")]
+    EcdsaP256Sha256,
+    #[serde(rename = "EdDSA")]
+    Ed25519
+}
+impl Algorithm {
+    pub const ALL: [Self; 4] = [Self::RsaPkcs1Sha256, Self::RsaPssSha256, Self::EcdsaP256Sha256, Self::Ed25519];
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::RsaPkcs1Sha256 => "RS256",
+            Self::RsaPssSha256 => "PS256",
+            Self::EcdsaP256Sha256 => "ES256",
+            Self::Ed25519 => "EdDSA"
+        }
+    }
+    /// The algorithm assumed for certificates signed before algorithms were declared
+    fn legacy() -> Self {
+        Self::RsaPkcs1Sha256
+    }
+    /// The algorithm stored for a user, or for users registered before algorithms were declared, the only
+    /// algorithm then supported for the kind of key
+    fn stored(algorithm: Option<Self>, pubkey: &str) -> Self {
+        algorithm.unwrap_or_else(|| match PKey::public_key_from_pem(pubkey.as_bytes()).map(|key| key.id()) {
+            Ok(Id::EC) => Self::EcdsaP256Sha256,
+            Ok(Id::ED25519) => Self::Ed25519,
+            _ => Self::RsaPkcs1Sha256
+        })
+    }
+    /// Check that `key` is of the kind used by the algorithm
+    pub fn check_key<T: HasPublic>(self, key: &PKeyRef<T>) -> Result<()> {
+        let matches = match self {
+            Self::RsaPkcs1Sha256 | Self::RsaPssSha256 => key.id() == Id::RSA,
+            Self::EcdsaP256Sha256 => key.id() == Id::EC && key.ec_key()?.group().curve_name() == Some(Nid::X9_62_PRIME256V1),
+            Self::Ed25519 => key.id() == Id::ED25519
+        };
+        if matches {
+            Ok(())
+        } else {
+            Err(Error::Algorithm(self))
+        }
+    }
+    /// Sign `data` with the private key, as `verify` expects
+    pub fn sign(self, key: &PKeyRef<Private>, data: &[u8]) -> Result<Vec<u8>> {
+        self.check_key(key)?;
+        let mut signer = match self {
+            Self::Ed25519 => Signer::new_without_digest(key)?,
+            _ => Signer::new(MessageDigest::sha256(), key)?
+        };
+        if self == Self::RsaPssSha256 {
+            signer.set_rsa_padding(Padding::PKCS1_PSS)?;
+            signer.set_rsa_mgf1_md(MessageDigest::sha256())?;
+            signer.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
+        }
+        Ok(signer.sign_oneshot_to_vec(data)?)
+    }
+    /// Verify a signature over `data` by the PEM encoded public key
+    pub fn verify(self, pubkey: &str, data: &[u8], signature: &[u8]) -> Result<bool> {
+        let pubkey = PKey::public_key_from_pem(pubkey.as_bytes())?;
+        self.check_key(&pubkey)?;
+        let mut verifier = match self {
+            // Ed25519 hashes the message itself
+            Self::Ed25519 => Verifier::new_without_digest(&pubkey)?,
+            _ => Verifier::new(MessageDigest::sha256(), &pubkey)?
+        };
+        if self == Self::RsaPssSha256 {
+            verifier.set_rsa_padding(Padding::PKCS1_PSS)?;
+            verifier.set_rsa_mgf1_md(MessageDigest::sha256())?;
+            verifier.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
+        }
+        Ok(verifier.verify_oneshot(signature, data)?)
+    }
+}
+impl std::str::FromStr for Algorithm {
+    type Err = Error;
+    fn from_str(name: &str) -> Result<Self> {
+        Self::ALL.into_iter()
+            .find(|algorithm| algorithm.name() == name)
+            .ok_or_else(|| Error::KeyPolicy(format!("Unknown signature algorithm {:?}", name)))
+    }
+}
+impl rusqlite::ToSql for Algorithm {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(rusqlite::types::ToSqlOutput::from(self.name()))
+    }
+}
+impl rusqlite::types::FromSql for Algorithm {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        value.as_str()?.parse().map_err(|_| rusqlite::types::FromSqlError::InvalidType)
+    }
+}
+
+/// The keys that are accepted when registering
+#[derive(Debug, Clone)]
+pub struct KeyPolicy {
+    pub algorithms: Vec<Algorithm>,
+    pub min_rsa_bits: u32
+}
+impl Default for KeyPolicy {
+    fn default() -> Self {
+        Self {
+            algorithms: Algorithm::ALL.to_vec(),
+            min_rsa_bits: 2048
+        }
+    }
+}
+
+/// Data signed by a user's key pair.
+/// 
+/// The signature is over the exact bytes of the `data` value as they appear in the request, so the value
+/// is never re-serialised for verification. Clients should serialise the data once, sign those bytes,
+/// then embed them unchanged, for example `{"data":{"name":"単語"},"signature":"..."}` where the signature
+/// is over the 17 bytes of `{"name":"単語"}`.
+#[derive(Clone)]
 pub struct Signed<T> {
     data: T,
-    #[serde(with = "serde_base64")]
+    raw: Box<RawValue>,
     signature: Vec<u8>
 }
-impl Signed<String> {
-    /// Verify the signature, assuming that the string refers to a user
+impl<T: Serialize> Signed<T> {
+    /// Serialise `data` and sign it with `sign`
+    pub fn new(data: T, sign: impl FnOnce(&[u8]) -> Result<Vec<u8>>) -> Result<Self> {
+        let raw = serde_json::value::to_raw_value(&data)?;
+        let signature = sign(raw.get().as_bytes())?;
+        Ok(Self {
+            data,
+            raw,
+            signature
+        })
+    }
+}
+impl<T> Signed<T> {
+    /// The bytes that were signed
+    fn unsigned(&self) -> &[u8] {
+        self.raw.get().as_bytes()
+    }
+}
+impl<T> Serialize for Signed<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("Signed", 2)?;
+        s.serialize_field("data", &self.raw)?;
+        s.serialize_field("signature", &base64::encode(&self.signature))?;
+        s.end()
+    }
+}
+impl<'de, T: serde::de::DeserializeOwned> Deserialize<'de> for Signed<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Envelope {
+            data: Box<RawValue>,
+            #[serde(with = "serde_base64")]
+            signature: Vec<u8>
+        }
+        let envelope = Envelope::deserialize(deserializer)?;
+        let data = serde_json::from_str(envelope.data.get())
+            .map_err(serde::de::Error::custom)?;
+        Ok(Self {
+            data,
+            raw: envelope.data,
+            signature: envelope.signature
+        })
+    }
+}
+/// Described by hand to match the hand written serialisation
+impl<T: ToSchema> utoipa::__dev::ComposeSchema for Signed<T> {
+    fn compose(generics: Vec<utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>>) -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        use utoipa::openapi::schema::{KnownFormat, ObjectBuilder, SchemaFormat, Type};
+        let data = generics.into_iter().next().unwrap_or_else(T::schema);
+        ObjectBuilder::new()
+            .property("data", data)
+            .required("data")
+            .property("signature", ObjectBuilder::new()
+                .schema_type(Type::String)
+                .format(Some(SchemaFormat::KnownFormat(KnownFormat::Byte)))
+                .description(Some("The base64 encoded signature of `data`"))
+            )
+            .required("signature")
+            .into()
+    }
+}
+impl<T: ToSchema> ToSchema for Signed<T> {
+    fn schemas(schemas: &mut Vec<(String, utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>)>) {
+        T::schemas(schemas);
+    }
+}
+impl Signed<ChallengeRequest> {
+    /// Verify the signature of the user requesting a challenge
     /// This is not suitable for verifying a request.
     /// Instead, this allows a user to get a challenge that can be used to correctly validate a request. 
-    pub fn verify_user(self, c: &Connection) -> Result<String> {
-        let pubkey = User::pubkey(c, &self.data)?
-            .ok_or(Error::NoUser(self.data.clone()))?;
-        let pubkey = openssl::pkey::PKey::public_key_from_pem(pubkey.as_bytes())?;
-        let mut verifier = openssl::sign::Verifier::new(openssl::hash::MessageDigest::sha256(), &pubkey)?;
-        let unsigned = serde_json::to_vec(&self.data)?;
-        if verifier.verify_oneshot(&self.signature, &unsigned)? {
+    pub fn verify_user(self, c: &Connection) -> Result<ChallengeRequest> {
+        let (pubkey, algorithm, _) = User::credentials(c, self.data.user())?
+            .ok_or_else(|| Error::NoUser(self.data.user().to_string()))?;
+        if algorithm.verify(&pubkey, self.unsigned(), &self.signature)? {
             Ok(self.data)
         } else {
             Err(Error::Signature)
@@ -121,32 +355,44 @@ impl Signed<Certificate> {
     /// A valid certificate ensures that the other end owns the private key for a given public key.
     /// It does not, however, guarantee anything about the user that presented the certificate, such as ownership of an account.
     /// Certificates should only be used to establish an account, otherwise they must be signed by the old key pair when rotating keys.
-    pub fn verify(self) -> Result<Certificate> {
-        let pubkey = openssl::pkey::PKey::public_key_from_pem(self.data.pubkey.as_bytes())?;
-        let unsigned = serde_json::to_vec(&self.data)?;
-        let mut verifier = openssl::sign::Verifier::new(openssl::hash::MessageDigest::sha256(), &pubkey)?;
-        if verifier.verify_oneshot(&self.signature, &unsigned)? {
+    /// 
+    /// The key must also satisfy the `policy` of the server.
+    pub fn verify(self, policy: &KeyPolicy) -> Result<Certificate> {
+        let algorithm = self.data.algorithm;
+        if !policy.algorithms.contains(&algorithm) {
+            return Err(Error::KeyPolicy(format!("The {} signature algorithm is not allowed", algorithm.name())))
+        }
+        let pubkey = PKey::public_key_from_pem(self.data.pubkey.as_bytes())?;
+        if pubkey.id() == Id::RSA && pubkey.bits() < policy.min_rsa_bits {
+            return Err(Error::KeyPolicy(format!("RSA keys must be at least {} bits", policy.min_rsa_bits)))
+        }
+        if algorithm.verify(&self.data.pubkey, self.unsigned(), &self.signature)? {
             Ok(self.data)
         } else {
             Err(Error::Signature)
         }
     }
 }
-impl<T: serde::Serialize> Signed<By<T>> {
-    pub fn privileged(self, c: &Connection) -> Result<T> {
-        self.verify(c, data::Privilege::Admin)
+impl<T> Signed<By<T>> {
+    pub fn privileged(self, c: &Connection, request: &Scope) -> Result<T> {
+        self.verify(c, data::Privilege::Admin, request)
     }
     /// Verify that a request made by a user is valid. This includes challenege verification to prevent replay attacks.
-    pub fn verify(self, c: &Connection, requires_privilege: data::Privilege) -> Result<T> {
-        let (pubkey, privilege) = User::credentials(c, &self.data.user)?
+    /// 
+    /// `request` describes the request being made, which the challenge must have been issued for.
+    pub fn verify(self, c: &Connection, requires_privilege: data::Privilege, request: &Scope) -> Result<T> {
+        self.identify(c, requires_privilege, request)
+            .map(|(_, data)| data)
+    }
+    /// Verify the request as with `verify`, also returning the name of the user that made it
+    #[tracing::instrument(level = "debug", skip(self, c), fields(user = %self.data.user))]
+    pub fn identify(self, c: &Connection, requires_privilege: data::Privilege, request: &Scope) -> Result<(String, T)> {
+        let (pubkey, algorithm, privilege) = User::credentials(c, &self.data.user)?
             .ok_or(Error::NoUser(self.data.user.clone()))?;
-        let pubkey = openssl::pkey::PKey::public_key_from_pem(pubkey.as_bytes())?;
-        let mut verifier = openssl::sign::Verifier::new(openssl::hash::MessageDigest::sha256(), &pubkey)?;
-        let unsigned = serde_json::to_vec(&self.data)?;
-        if verifier.verify_oneshot(&self.signature, &unsigned)? {
-            if Challenge::verify(c, &self.data.challenge, &self.data.user)? {
+        if algorithm.verify(&pubkey, self.unsigned(), &self.signature)? {
+            if Challenge::verify(c, &self.data.challenge, &self.data.user, request)? {
                 if privilege >= requires_privilege {
-                    Ok(self.data.data)
+                    Ok((self.data.user, self.data.data))
                 } else {
                     Err(Error::Privilege)
                 }
@@ -160,19 +406,33 @@ impl<T: serde::Serialize> Signed<By<T>> {
 }
 
 /// An identity for pubkey lookup
-#[derive(Serialize, Clone, Deserialize)]
+#[derive(Serialize, Clone, Deserialize, ToSchema)]
 pub struct By<T> {
     user: String,
+    /// A challenge issued to the user by `/v1/auth/challenge`
     challenge: String,
     data: T,
 }
+impl<T> By<T> {
+    /// Data sent by `user`, authorised by a `challenge` issued to them
+    pub fn new(user: String, challenge: String, data: T) -> Self {
+        Self {
+            user,
+            challenge,
+            data
+        }
+    }
+}
 
-#[derive(Serialize, Clone, Deserialize)]
+#[derive(Serialize, Clone, Deserialize, ToSchema)]
 pub struct Certificate {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub contact: Option<String>,
+    /// A PEM encoded public key
     pub pubkey: String,
+    #[serde(default = "Algorithm::legacy")]
+    pub algorithm: Algorithm,
     pub created: DateTime<Utc>
 }
 
@@ -183,28 +443,268 @@ pub struct Challenge {
     pub expires: DateTime<Utc>
 }
 impl Challenge {
-    /// Create and register a new base64 encoded challenge for the given user
-    pub fn generate(c: &Connection, user: &str) -> Result<String> {
+    /// Create and register a new base64 encoded challenge for the given user, only usable for requests within `scope`
+    #[tracing::instrument(level = "debug", skip(c))]
+    pub fn generate(c: &Connection, user: &str, scope: &Scope) -> Result<String> {
         let mut challenge = [0; 32];
         openssl::rand::rand_bytes(&mut challenge)?;
-        let challenge = base64::encode(&challenge);
-        let mut s = c.prepare("INSERT INTO `challenge` (`user`, `challenge`, `expires`) SELECT `user`.`rowid`, ?1 AS `challenge`, ?2 AS `expires` FROM `user` WHERE `user`.`name` = ?3")?;
+        let challenge = base64::encode(challenge);
+        let mut s = c.prepare("INSERT INTO `challenge` (`user`, `challenge`, `expires`, `method`, `path`, `purpose`) SELECT `user`.`rowid`, ?1 AS `challenge`, ?2 AS `expires`, ?4 AS `method`, ?5 AS `path`, ?6 AS `purpose` FROM `user` WHERE `user`.`name` = ?3")?;
         let expires = Utc::now() + chrono::Duration::minutes(5);
-        s.execute(rusqlite::params!(&challenge, expires, user))?;
+        s.execute(rusqlite::params!(&challenge, expires, user, &scope.method, &scope.path, &scope.purpose))?;
+        crate::metrics::challenge("issued");
         Ok(challenge)
     }
-    fn prune_old(c: &Connection) -> Result<()> {
-        let mut s = c.prepare("DELETE FROM `challenge` WHERE `challenge`.`expires` < ?1")?;
-        s.execute([Utc::now()])?;
-        Ok(())
+    /// Get the number of unexpired challenges issued to the user, and when the first of them expires
+    pub fn outstanding(c: &Connection, user: &str) -> Result<(u32, Option<DateTime<Utc>>)> {
+        c.prepare("SELECT COUNT(*), MIN(`challenge`.`expires`) FROM `challenge` WHERE `challenge`.`expires` >= ?1 AND `challenge`.`user` = (SELECT `user`.`rowid` FROM `user` WHERE `user`.`name` = ?2)")?
+            .query_row(rusqlite::params!(Utc::now(), user), |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.into())
+    }
+    /// Remove expired challenges, returning how many there were
+    pub fn prune_old(c: &Connection) -> Result<usize> {
+        let mut s = c.prepare_cached("DELETE FROM `challenge` WHERE `challenge`.`expires` < ?1")?;
+        Ok(s.execute([Utc::now()])?)
     }
     /// Checks a challenge, removing it if it exists
     /// 
-    /// Returns true if the challenge was valid
-    pub fn verify(c: &Connection, challenge: &str, user: &str) -> Result<bool> {
+    /// Returns true if the challenge was valid and issued for the `request`.
+    /// A challenge is used up even if it was presented with a request outside of its scope.
+    #[tracing::instrument(level = "debug", skip(c, challenge))]
+    pub fn verify(c: &Connection, challenge: &str, user: &str, request: &Scope) -> Result<bool> {
         // Ensure we are not including old challenges
         Self::prune_old(c)?;
-        let mut s = c.prepare("DELETE FROM `challenge` WHERE `challenge`.`challenge` = ?1 AND `challenge`.`user` = (SELECT `user`.`rowid` FROM `user` WHERE `user`.`name` = ?2)")?;
-        Ok(s.execute(rusqlite::params!(challenge, user))? == 1)
+        let scope = c.prepare("SELECT `method`, `path`, `purpose` FROM `challenge` WHERE `challenge`.`challenge` = ?1 AND `challenge`.`user` = (SELECT `user`.`rowid` FROM `user` WHERE `user`.`name` = ?2)")?
+            .query_row(rusqlite::params!(challenge, user), |row| Ok(Scope {
+                method: row.get(0)?,
+                path: row.get(1)?,
+                purpose: row.get(2)?
+            }))
+            .optional()?;
+        let scope = match scope {
+            Some(scope) => scope,
+            None => {
+                crate::metrics::challenge("rejected");
+                return Ok(false)
+            }
+        };
+        let mut s = c.prepare("DELETE FROM `challenge` WHERE `challenge`.`challenge` = ?1")?;
+        // Another request may have used the challenge in the mean time
+        let valid = s.execute([challenge])? == 1 && scope.permits(request);
+        crate::metrics::challenge(if valid { "verified" } else { "rejected" });
+        Ok(valid)
+    }
+}
+
+/// The requests that a challenge may authorise. Any part that is `None` is unrestricted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct Scope {
+    /// The HTTP method of the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// The path of the request, as sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// The action being authorised, such as `set_word`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purpose: Option<String>
+}
+impl Scope {
+    /// Check that this scope allows the given `request`
+    pub fn permits(&self, request: &Scope) -> bool {
+        fn part(allowed: &Option<String>, actual: &Option<String>, eq: fn(&str, &str) -> bool) -> bool {
+            match (allowed, actual) {
+                (None, _) => true,
+                (Some(allowed), Some(actual)) => eq(allowed, actual),
+                (Some(_), None) => false
+            }
+        }
+        part(&self.method, &request.method, |a, b| a.eq_ignore_ascii_case(b))
+            && part(&self.path, &request.path, |a, b| a == b)
+            && part(&self.purpose, &request.purpose, |a, b| a == b)
+    }
+}
+
+/// A request for a challenge, signed by the user that will use it
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum ChallengeRequest {
+    /// Just the name of the user, for a challenge that can authorise any of their requests
+    User(String),
+    /// A challenge that can only authorise requests within the scope
+    Scoped {
+        user: String,
+        scope: Scope
+    }
+}
+impl ChallengeRequest {
+    pub fn user(&self) -> &str {
+        match self {
+            Self::User(user) | Self::Scoped { user, .. } => user
+        }
+    }
+    pub fn scope(&self) -> Scope {
+        match self {
+            Self::User(_) => Scope::default(),
+            Self::Scoped { scope, .. } => scope.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::ec::{EcGroup, EcKey};
+
+    fn public_pem<T: HasPublic>(key: &PKeyRef<T>) -> String {
+        String::from_utf8(key.public_key_to_pem().unwrap()).unwrap()
+    }
+    fn ec_key() -> PKey<Private> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn undeclared_algorithm_follows_the_key() {
+        let pool = data::memory();
+        let c = pool.get().unwrap();
+        let rsa = PKey::from_rsa(openssl::rsa::Rsa::generate(2048).unwrap()).unwrap();
+        let ec = ec_key();
+        let mut s = c.prepare("INSERT INTO `user` (`name`, `privilege`, `pubkey`) VALUES (?1, 0, ?2)").unwrap();
+        s.execute(["rsa", public_pem(&rsa).as_str()]).unwrap();
+        s.execute(["ec", public_pem(&ec).as_str()]).unwrap();
+
+        assert_eq!(User::credentials(&c, "rsa").unwrap().unwrap().1, Algorithm::RsaPkcs1Sha256);
+        assert_eq!(User::credentials(&c, "ec").unwrap().unwrap().1, Algorithm::EcdsaP256Sha256);
+        assert_eq!(User::named(&c, "ec").unwrap().unwrap().algorithm, Algorithm::EcdsaP256Sha256);
+    }
+
+    /// Register a user with a new key, returning the key
+    fn register(c: &Connection, name: &str) -> PKey<Private> {
+        let key = ec_key();
+        User {
+            name: name.into(),
+            contact: None,
+            image: None,
+            privilege: Privilege::Admin,
+            pubkey: public_pem(&key),
+            algorithm: Algorithm::EcdsaP256Sha256
+        }.insert(c).unwrap();
+        key
     }
-}
\ No newline at end of file
+    fn scope(method: &str, path: &str, purpose: &str) -> Scope {
+        Scope {
+            method: Some(method.into()),
+            path: Some(path.into()),
+            purpose: Some(purpose.into())
+        }
+    }
+    /// Sign a request by `user` with a challenge issued for `issued`, then verify it as the `request`
+    fn replay(c: &Connection, key: &PKey<Private>, issued: &Scope, request: &Scope) -> Result<()> {
+        let challenge = Challenge::generate(c, "user", issued).unwrap();
+        let signed = Signed::new(By::new("user".into(), challenge, ()), |bytes| Algorithm::EcdsaP256Sha256.sign(key, bytes)).unwrap();
+        signed.verify(c, Privilege::None, request)
+    }
+    fn challenges(c: &Connection) -> i64 {
+        c.query_row("SELECT COUNT(*) FROM `challenge`", [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn challenge_is_only_valid_for_its_request() {
+        let pool = data::memory();
+        let c = pool.get().unwrap();
+        let key = register(&c, "user");
+        let login = scope("POST", "/v1/auth/login", "login");
+
+        let verified = replay(&c, &key, &login, &login);
+        assert!(verified.is_ok());
+        let verified = replay(&c, &key, &login, &scope("POST", "/v1/word/x", "set_word"));
+        assert!(matches!(verified, Err(Error::Challenge)), "a login challenge authorised setting a word");
+        let verified = replay(&c, &key, &login, &scope("GET", "/v1/auth/login", "login"));
+        assert!(matches!(verified, Err(Error::Challenge)), "a challenge authorised another method");
+        let verified = replay(&c, &key, &Scope::default(), &scope("POST", "/v1/word/x", "set_word"));
+        assert!(verified.is_ok(), "an unscoped challenge was rejected");
+    }
+
+    #[test]
+    fn challenge_is_consumed_when_rejected() {
+        let pool = data::memory();
+        let c = pool.get().unwrap();
+        register(&c, "user");
+        let login = scope("POST", "/v1/auth/login", "login");
+
+        for request in [scope("POST", "/v1/word/x", "set_word"), scope("get", "/v1/auth/login", "login"), login.clone()] {
+            let challenge = Challenge::generate(&c, "user", &login).unwrap();
+            Challenge::verify(&c, &challenge, "user", &request).unwrap();
+            assert_eq!(challenges(&c), 0);
+            assert!(!Challenge::verify(&c, &challenge, "user", &login).unwrap(), "a challenge was used twice");
+        }
+    }
+
+    /// Signed by each key below. The keys are in the opposite order to how `ChallengeRequest` serialises,
+    /// with extra whitespace, as the signature is over the bytes sent rather than a re-serialisation.
+    const VECTOR_DATA: &str = r#"{ "scope" : { "purpose" : "login",  "method" : "POST" },
+  "user" : "alice" }"#;
+    const VECTOR_RSA: &str = "\
+-----BEGIN PUBLIC KEY-----
+MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEA1lOgAlwleauwhVFYAmjx
+rXUPrFQQBCzdKVBZzZBAe8LDdoU/mkREOGIFheRvNcf7Z6D3p/IerOQQqH7lp4FG
+q8oVB2YKL52u0sRX9D3pXis/VhwTpU6R1H5g+hFWGYPFClhZFR7EK/KxZhcfmFTX
+BEeZ6OeewlS34mo4yj71ExMHO0AdAn01RZbzZU3KUeYQbzY0GzROvZSmYW49K+wF
+kiPMZEQ5p6aT8qlz60x//rNT1HsTduIMqXGolOMiQt0cHkuOiEF3Bmf9s2Fug0MI
+ukipaWbtslVO6mCIPL6R+rRUwCLpkiAdC+/q5tcJFWfCtMsrrtAd7SjcB/snSA+/
+CQIDAQAB
+-----END PUBLIC KEY-----\n";
+    const VECTOR_EC: &str = "\
+-----BEGIN PUBLIC KEY-----
+MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEM8o8V49o5VOlTAVSA8Nrbf4bh0oZ
+6PnDmI+GiG/WFHrGpR/ODVx+2Oj48fh6I/eVcSZ/uwahoNoiE0dWyfw3yg==
+-----END PUBLIC KEY-----\n";
+    const VECTOR_ED25519: &str = "\
+-----BEGIN PUBLIC KEY-----
+MCowBQYDK2VwAyEAkKX4TqmLenn0ySwmfgFRREqLjut5Az7D1KivyvYQ+eo=
+-----END PUBLIC KEY-----\n";
+    /// The algorithm, public key and base64 encoded signature of each test vector
+    const VECTORS: &[(Algorithm, &str, &str)] = &[
+        (Algorithm::RsaPkcs1Sha256, VECTOR_RSA, "xJUtSkHBgWStRq1HocnaxSSt+RENiMPb2fOWrj7X6Fw7dwsgwkOgAtXisQARRgKoNhv5u1bvLX0J/2oYAXQDF6xDM4RqBck/nSE/Pw+3dnzVYrxobHkwtxuEH3ft0tLJnpDBxwaBqvq+5HKBj1bAl6mukHwSzzuwCMpVcCjQcz+RtAGaZqCjmvf2QFhZg973Jaqwzw6exdjE85vF2oyu2GwOE201Vw+FH12k3MuFG+kaWmSr64vMJKI9NQlqDOrHTAiK9PgSWKMwAub42SdVDK65T2menZPaDqZyArw5IynEEaPy6KyuexugC/kB11r43t1zZNvWO/0uvTOfwBfXdg=="),
+        (Algorithm::RsaPssSha256, VECTOR_RSA, "a0lXyo63NQy6AjDWOsYmiq8IcmjZLJtCqcYAzLsHla1/YeSpC+qTNcYvssoG346JRd6d+yA2yLAv7LfkovphdNVyOa6sK73a1hY1kmnQa0SlLqDAuY0cIbzs7PFhPwyR8kBBMQCbqHfGsRmSkxgfNJTmyAeV71KboDPKWBn+H3sC4LhCpNnp/5VOhwGx42KDnypNn7pG41nftVrP3BMeK+8h8cmwMB/19qatEXTBSlBn0f+x/o7KCaH73EfleA7qTImGMEhRDlItzzYbzGPR68C/UldOzhfZ7RgfPchzb4kJ0TlH+f5hM2v4v/tWIAU7bLe/2tmB/XMDdg4VJGkfoQ=="),
+        (Algorithm::EcdsaP256Sha256, VECTOR_EC, "MEUCICoIumYeYMAyeyLw1KyIYco6Mjonj+vKHcMfoT0cdg+OAiEAvVOGsbQWOodc27u5OMIgNfYJb2qKMREqFjX5hEXMS7s="),
+        (Algorithm::Ed25519, VECTOR_ED25519, "FYuiCliOMORUMPJTW689nwyCZWMaMHbu2h51isa/7e+GZUQNjVK+1nvtzOyUKQqu3kKcpUh+HENS+wwPyrYIBA==")
+    ];
+
+    fn envelope(data: &str, signature: &str) -> String {
+        format!(r#"{{"data":{},"signature":"{}"}}"#, data, signature)
+    }
+    /// Whether the request `body` is signed by `pubkey`
+    fn verifies(algorithm: Algorithm, pubkey: &str, body: &str) -> bool {
+        let signed: Signed<ChallengeRequest> = serde_json::from_str(body).unwrap();
+        matches!(algorithm.verify(pubkey, signed.unsigned(), &signed.signature), Ok(true))
+    }
+
+    #[test]
+    fn signature_vectors() {
+        for &(algorithm, pubkey, signature) in VECTORS {
+            let name = algorithm.name();
+            let signed: Signed<ChallengeRequest> = serde_json::from_str(&envelope(VECTOR_DATA, signature)).unwrap();
+            assert_eq!(signed.data.user(), "alice");
+            assert_eq!(signed.data.scope().purpose.as_deref(), Some("login"));
+
+            assert!(verifies(algorithm, pubkey, &envelope(VECTOR_DATA, signature)), "{} rejected its vector", name);
+            assert!(!verifies(algorithm, pubkey, &envelope(&VECTOR_DATA.replace("alice", "alicf"), signature)), "{} accepted tampered data", name);
+            assert!(!verifies(algorithm, pubkey, &envelope(&VECTOR_DATA.replacen(' ', "", 1), signature)), "{} ignored whitespace", name);
+            let mut tampered = base64::decode(signature).unwrap();
+            let last = tampered.len() - 1;
+            tampered[last] ^= 1;
+            assert!(!verifies(algorithm, pubkey, &envelope(VECTOR_DATA, &base64::encode(&tampered))), "{} accepted a tampered signature", name);
+        }
+        // The RSA schemes share a key, but not signatures
+        assert!(!verifies(Algorithm::RsaPssSha256, VECTOR_RSA, &envelope(VECTOR_DATA, VECTORS[0].2)));
+        assert!(!verifies(Algorithm::RsaPkcs1Sha256, VECTOR_RSA, &envelope(VECTOR_DATA, VECTORS[1].2)));
+    }
+
+    #[test]
+    fn undeclared_certificate_algorithm_is_rs256() {
+        let certificate: Certificate = serde_json::from_str(r#"{"name":"a","pubkey":"","created":"2022-01-01T00:00:00Z"}"#).unwrap();
+        assert_eq!(certificate.algorithm, Algorithm::RsaPkcs1Sha256);
+    }
+}
@@ -1,65 +1,334 @@
 use super::prelude::*;
 
-#[derive(Debug, Serialize, Deserialize)]
+use std::collections::HashMap;
+
+/// Identifies a version of a dictionary entry
+#[derive(Debug, Clone, Default)]
+pub struct Revision {
+    /// The rowid of the entry
+    pub id: i64,
+    /// Incremented every time the entry is modified
+    pub revision: i64,
+    pub modified: Option<DateTime<Utc>>
+}
+impl Revision {
+    /// Read the revision from the `rowid`, `revision` and `modified` columns starting at `index`
+    fn from_row(row: &rusqlite::Row, index: usize) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(index)?,
+            revision: row.get(index + 1)?,
+            modified: row.get(index + 2)?
+        })
+    }
+    /// An opaque tag that changes whenever the entry does
+    pub fn tag(&self) -> String {
+        format!("{}-{}", self.id, self.revision)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Word {
     pub word: String,
     pub readings: Vec<WordReading>,
-    pub tags: Vec<data::Tag>
+    pub tags: Vec<data::Tag>,
+    #[serde(skip)]
+    pub revision: Revision
 }
 impl Word {
+    #[tracing::instrument(level = "debug", skip(c))]
     pub fn get(c: &Connection, word: &str) -> Result<Option<Self>> {
-        Ok(c.prepare("SELECT `word`, `rowid` FROM `word` WHERE `word` = ?1")?
-            .query_row([word], |row| {
-                let word_id = row.get(1)?;
-                Ok(Self {
-                    word: row.get(0)?,
-                    readings: WordReading::for_word(c, word_id)?,
-                    tags: data::Tag::for_word(c, word_id)?
-                })
-            })
-            .optional()?
-        )
+        let found = c.prepare_cached("SELECT `word`, `rowid`, `revision`, `modified` FROM `word` WHERE `word` = ?1")?
+            .query_row([word], |row| Ok((row.get::<_, String>(0)?, Revision::from_row(row, 1)?)))
+            .optional()?;
+        Ok(match found {
+            Some((word, revision)) => Some(Self {
+                word,
+                readings: WordReading::for_word(c, revision.id as u64)?,
+                tags: data::Tag::for_word(c, revision.id as u64)?,
+                revision
+            }),
+            None => None
+        })
     }
+    /// Get every word in `words` that exists, keyed by the word
+    #[tracing::instrument(level = "debug", skip(c))]
+    pub fn get_many(c: &Connection, words: &[String]) -> Result<HashMap<String, Self>> {
+        if words.is_empty() {
+            return Ok(HashMap::new())
+        }
+        let placeholders = placeholders(words.len());
+
+        let mut found = HashMap::new();
+        let mut s = c.prepare_cached(&format!("SELECT `word`, `rowid`, `revision`, `modified` FROM `word` WHERE `word` IN ({})", placeholders))?;
+        let rows = s.query_map(rusqlite::params_from_iter(words), |row| Ok((row.get::<_, String>(0)?, Revision::from_row(row, 1)?)))?;
+        for row in rows {
+            let (word, revision) = row?;
+            found.insert(revision.id, Self {
+                word,
+                readings: Vec::new(),
+                tags: Vec::new(),
+                revision
+            });
+        }
+
+        let mut s = c.prepare_cached(&format!("SELECT `word_reading`.`word`, `word_reading`.`rowid`, `word_reading`.`full`, `word_reading`.`accent`, `definition`.`definition`
+            FROM `word`
+            INNER JOIN `word_reading` ON `word_reading`.`word` = `word`.`rowid`
+            LEFT JOIN `definition` ON `definition`.`word_reading` = `word_reading`.`rowid`
+            WHERE `word`.`word` IN ({})
+            ORDER BY `word_reading`.`rowid`, `definition`.`rowid`", placeholders))?;
+        let mut rows = s.query(rusqlite::params_from_iter(words))?;
+        // Rows for the same reading are adjacent, one per definition
+        let mut last_reading = None;
+        while let Some(row) = rows.next()? {
+            let word_id: i64 = row.get(0)?;
+            let reading_id: i64 = row.get(1)?;
+            let word = match found.get_mut(&word_id) {
+                Some(word) => word,
+                None => continue
+            };
+            if last_reading != Some(reading_id) {
+                last_reading = Some(reading_id);
+                word.readings.push(WordReading {
+                    full: row.get(2)?,
+                    accent: row.get(3)?,
+                    definitions: Vec::new()
+                });
+            }
+            if let (Some(reading), Some(definition)) = (word.readings.last_mut(), row.get::<_, Option<String>>(4)?) {
+                reading.definitions.push(Definition { definition });
+            }
+        }
+
+        let mut s = c.prepare_cached(&format!("SELECT `word_tag`.`word`, `tag`.`tag`
+            FROM `word`
+            INNER JOIN `word_tag` ON `word_tag`.`word` = `word`.`rowid`
+            INNER JOIN `tag` ON `word_tag`.`tag` = `tag`.`rowid`
+            WHERE `word`.`word` IN ({})", placeholders))?;
+        let rows = s.query_map(rusqlite::params_from_iter(words), |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+        for row in rows {
+            let (word_id, tag) = row?;
+            if let Some(word) = found.get_mut(&word_id) {
+                word.tags.push(data::Tag { tag });
+            }
+        }
+
+        Ok(found.into_values().map(|word| (word.word.clone(), word)).collect())
+    }
+    /// Add a new word at its first revision, failing with `Error::Exists` if it already exists
+    #[tracing::instrument(level = "debug", skip(self, c), fields(word = %self.word))]
     pub fn insert(self, c: &Connection) -> Result<()> {
-        let word_id = c.prepare("INSERT INTO `word` (`word`) VALUES (?1)")?
-            .insert([&self.word])?;
+        let transaction = c.unchecked_transaction()?;
+        let inserted = c.prepare("INSERT OR IGNORE INTO `word` (`word`, `revision`, `modified`) VALUES (?1, 1, ?2)")?
+            .execute(rusqlite::params!(&self.word, Utc::now()))?;
+        if inserted != 1 {
+            return Err(Error::Exists(self.word))
+        }
+        self.insert_entries(c, c.last_insert_rowid())?;
+        transaction.commit()?;
+        Ok(())
+    }
+    /// Replace the readings and tags of an existing word, moving it to the next revision.
+    /// Links between the old readings and kanji are removed along with them.
+    ///
+    /// Returns false if there is no such word.
+    #[tracing::instrument(level = "debug", skip(self, c), fields(word = %self.word))]
+    pub fn update(self, c: &Connection) -> Result<bool> {
+        let transaction = c.unchecked_transaction()?;
+        // Writing first takes the lock before anything is read
+        let updated = c.prepare("UPDATE `word` SET `revision` = `revision` + 1, `modified` = ?2 WHERE `word` = ?1")?
+            .execute(rusqlite::params!(&self.word, Utc::now()))?;
+        if updated != 1 {
+            return Ok(false)
+        }
+        let word_id: i64 = c.query_row("SELECT `rowid` FROM `word` WHERE `word` = ?1", [&self.word], |row| row.get(0))?;
+        c.execute("DELETE FROM `reading` WHERE `word` IN (SELECT `rowid` FROM `word_reading` WHERE `word` = ?1)", [word_id])?;
+        c.execute("DELETE FROM `definition` WHERE `word_reading` IN (SELECT `rowid` FROM `word_reading` WHERE `word` = ?1)", [word_id])?;
+        c.execute("DELETE FROM `word_reading` WHERE `word` = ?1", [word_id])?;
+        c.execute("DELETE FROM `word_tag` WHERE `word` = ?1", [word_id])?;
+        self.insert_entries(c, word_id)?;
+        transaction.commit()?;
+        Ok(true)
+    }
+    /// Insert the readings, definitions and tags of the word with the rowid `word_id`
+    fn insert_entries(self, c: &Connection, word_id: i64) -> Result<()> {
         for reading in self.readings {
-            
+            let reading_id = c.prepare_cached("INSERT INTO `word_reading` (`word`, `full`, `accent`) VALUES (?1, ?2, ?3)")?
+                .insert(rusqlite::params!(word_id, reading.full, reading.accent))?;
+            for definition in reading.definitions {
+                c.prepare_cached("INSERT INTO `definition` (`word_reading`, `definition`) VALUES (?1, ?2)")?
+                    .execute(rusqlite::params!(reading_id, definition.definition))?;
+            }
         }
         for tag in self.tags {
             let tag_id = tag.get_or_insert(c)?;
-            c.prepare("INSERT INTO `word_tag` (`word`, `tag`) VALUES (?1, ?2)")?
+            c.prepare_cached("INSERT INTO `word_tag` (`word`, `tag`) VALUES (?1, ?2)")?
                 .execute([word_id, tag_id])?;
         }
         Ok(())
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The order of a word listing
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WordOrder {
+    /// Alphabetical by the first reading of the word
+    #[default]
+    Reading,
+    /// Most recently added first
+    Recent
+}
+
+/// A lightweight overview of a word for browsing
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WordSummary {
+    pub word: String,
+    pub reading: Option<String>
+}
+impl WordSummary {
+    /// The largest number of words that will be listed in a single page
+    pub const MAX_PAGE: u32 = 200;
+    const QUERY_READING: &'static str = "SELECT `id`, `word`, `reading` FROM (
+            SELECT `word`.`rowid` AS `id`, `word`.`word` AS `word`, (SELECT MIN(`word_reading`.`full`) FROM `word_reading` WHERE `word_reading`.`word` = `word`.`rowid`) AS `reading`
+            FROM `word`
+            WHERE ?1 IS NULL OR `word`.`rowid` IN (SELECT `word_tag`.`word` FROM `word_tag` INNER JOIN `tag` ON `word_tag`.`tag` = `tag`.`rowid` WHERE `tag`.`tag` = ?1)
+        )
+        WHERE ?2 IS NULL OR (IFNULL(`reading`, ''), `id`) > (?2, ?3)
+        ORDER BY IFNULL(`reading`, ''), `id`
+        LIMIT ?4";
+    const QUERY_RECENT: &'static str = "SELECT `word`.`rowid`, `word`.`word`, (SELECT MIN(`word_reading`.`full`) FROM `word_reading` WHERE `word_reading`.`word` = `word`.`rowid`)
+        FROM `word`
+        WHERE (?1 IS NULL OR `word`.`rowid` IN (SELECT `word_tag`.`word` FROM `word_tag` INNER JOIN `tag` ON `word_tag`.`tag` = `tag`.`rowid` WHERE `tag`.`tag` = ?1))
+            AND (?2 IS NULL OR `word`.`rowid` < ?2)
+        ORDER BY `word`.`rowid` DESC
+        LIMIT ?3";
+    /// List a page of at most `limit` words, continuing from the cursor `after` returned with a previous page.
+    /// 
+    /// Only words with `tag` are listed if one is given.
+    #[tracing::instrument(level = "debug", skip(c))]
+    pub fn page(c: &Connection, order: WordOrder, tag: Option<&str>, after: Option<&str>, limit: u32) -> Result<WordPage> {
+        let cursor = after.map(Cursor::decode).transpose()?;
+        // Fetch an extra row to find out if there is a following page
+        let fetch = limit + 1;
+        let mut s;
+        let rows = match order {
+            WordOrder::Reading => {
+                let (id, reading) = match cursor {
+                    Some(Cursor { id, reading }) => (Some(id), Some(reading.unwrap_or_default())),
+                    None => (None, None)
+                };
+                s = c.prepare_cached(Self::QUERY_READING)?;
+                s.query_map(rusqlite::params!(tag, reading, id, fetch), Self::from_row)?
+            },
+            WordOrder::Recent => {
+                s = c.prepare_cached(Self::QUERY_RECENT)?;
+                s.query_map(rusqlite::params!(tag, cursor.map(|cursor| cursor.id), fetch), Self::from_row)?
+            }
+        };
+        let mut words = Vec::new();
+        for word in rows {
+            words.push(word?)
+        }
+        let next = if words.len() > limit as usize {
+            words.truncate(limit as usize);
+            words.last().map(|(id, summary)| Cursor {
+                id: *id,
+                reading: match order {
+                    WordOrder::Reading => summary.reading.clone(),
+                    WordOrder::Recent => None
+                }
+            }.encode())
+        } else {
+            None
+        };
+        Ok(WordPage {
+            words: words.into_iter().map(|(_, summary)| summary).collect(),
+            next
+        })
+    }
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<(i64, Self)> {
+        Ok((row.get(0)?, Self {
+            word: row.get(1)?,
+            reading: row.get(2)?
+        }))
+    }
+}
+
+/// A page of a word listing
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WordPage {
+    pub words: Vec<WordSummary>,
+    /// The cursor for the following page, if there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>
+}
+
+/// The position of the last word in a page, opaque to clients
+struct Cursor {
+    id: i64,
+    reading: Option<String>
+}
+impl Cursor {
+    fn encode(&self) -> String {
+        match &self.reading {
+            Some(reading) => base64::encode_config(format!("{}:{}", self.id, reading), base64::URL_SAFE_NO_PAD),
+            None => base64::encode_config(self.id.to_string(), base64::URL_SAFE_NO_PAD)
+        }
+    }
+    fn decode(cursor: &str) -> Result<Self> {
+        let cursor = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| Error::Cursor)?;
+        let cursor = String::from_utf8(cursor)
+            .map_err(|_| Error::Cursor)?;
+        let (id, reading) = match cursor.split_once(':') {
+            Some((id, reading)) => (id, Some(reading.to_string())),
+            None => (cursor.as_str(), None)
+        };
+        Ok(Self {
+            id: id.parse().map_err(|_| Error::Cursor)?,
+            reading
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct WordReading {
     pub full: String,
     pub accent: String,
     pub definitions: Vec<Definition>
 }
 impl WordReading {
-    const QUERY: &'static str = "SELECT `full`, `accent`, `rowid` FROM `word_reading` WHERE `word` = ?1";
+    const QUERY: &'static str = "SELECT `word_reading`.`rowid`, `word_reading`.`full`, `word_reading`.`accent`, `definition`.`definition`
+        FROM `word_reading`
+        LEFT JOIN `definition` ON `definition`.`word_reading` = `word_reading`.`rowid`
+        WHERE `word_reading`.`word` = ?1
+        ORDER BY `word_reading`.`rowid`, `definition`.`rowid`";
+    /// Get the readings of a word along with their definitions in a single query
     pub fn for_word(c: &Connection, word: u64) -> rusqlite::Result<Vec<Self>> {
-        let mut s = c.prepare(Self::QUERY)?;
-        let rows = s.query_map([word], |row| {
-                Ok(Self {
-                    full: row.get(0)?,
-                    accent: row.get(1)?,
-                    definitions: Definition::for_reading(c, row.get(2)?)?
-                })
-            })?;
-        let mut readings = Vec::new();
-        for reading in rows {
-            readings.push(reading?)
+        let mut s = c.prepare_cached(Self::QUERY)?;
+        let mut rows = s.query([word])?;
+        let mut readings: Vec<Self> = Vec::new();
+        // Rows for the same reading are adjacent, one per definition
+        let mut last_reading = None;
+        while let Some(row) = rows.next()? {
+            let reading_id: i64 = row.get(0)?;
+            if last_reading != Some(reading_id) {
+                last_reading = Some(reading_id);
+                readings.push(Self {
+                    full: row.get(1)?,
+                    accent: row.get(2)?,
+                    definitions: Vec::new()
+                });
+            }
+            if let (Some(reading), Some(definition)) = (readings.last_mut(), row.get::<_, Option<String>>(3)?) {
+                reading.definitions.push(Definition { definition });
+            }
         }
         Ok(readings)
     }
 }
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Definition {
     pub definition: String,
 }
@@ -71,7 +340,7 @@ impl std::fmt::Display for Definition {
 impl Definition {
     const QUERY: &'static str = "SELECT `definition` FROM `definition` WHERE `definition`.`word_reading` = ?1";
     pub fn for_reading(c: &Connection, reading: u64) -> rusqlite::Result<Vec<Self>> {
-        let mut s = c.prepare(Self::QUERY)?;
+        let mut s = c.prepare_cached(Self::QUERY)?;
         let rows = s.query_map([reading], |row| {
                 Ok(Self {
                     definition: row.get(0)?
@@ -85,20 +354,45 @@ impl Definition {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct Kanji {
     pub kanji: char,
-    pub memonic: String
+    pub memonic: String,
+    #[serde(skip)]
+    pub revision: Revision
 }
 impl Kanji {
-    const QUERY: &'static str = "SELECT `kanji`, `memonic` FROM `kanji` WHERE `kanji` = ?1";
+    const QUERY: &'static str = "SELECT `kanji`, `memonic`, `rowid`, `revision`, `modified` FROM `kanji` WHERE `kanji` = ?1";
+    #[tracing::instrument(level = "debug", skip(c))]
     pub fn get(c: &Connection, kanji: char) -> rusqlite::Result<Option<Self>> {
-        Ok(c.prepare(Self::QUERY)?.query_row([kanji.to_string()], |row| {
+        c.prepare_cached(Self::QUERY)?.query_row([kanji.to_string()], |row| {
             Ok(Self {
                 kanji: row.get::<_, String>(0)?.chars().next().ok_or(rusqlite::Error::QueryReturnedNoRows)?,
-                memonic: row.get(1)?
+                memonic: row.get(1)?,
+                revision: Revision::from_row(row, 2)?
             })
-        }).optional()?)
+        }).optional()
+    }
+    /// Get every kanji in `kanji` that exists, keyed by the kanji
+    #[tracing::instrument(level = "debug", skip(c))]
+    pub fn get_many(c: &Connection, kanji: &[char]) -> Result<HashMap<char, Self>> {
+        if kanji.is_empty() {
+            return Ok(HashMap::new())
+        }
+        let mut s = c.prepare_cached(&format!("SELECT `kanji`, `memonic`, `rowid`, `revision`, `modified` FROM `kanji` WHERE `kanji` IN ({})", placeholders(kanji.len())))?;
+        let rows = s.query_map(rusqlite::params_from_iter(kanji.iter().map(char::to_string)), |row| {
+            Ok(Self {
+                kanji: row.get::<_, String>(0)?.chars().next().ok_or(rusqlite::Error::QueryReturnedNoRows)?,
+                memonic: row.get(1)?,
+                revision: Revision::from_row(row, 2)?
+            })
+        })?;
+        let mut found = HashMap::new();
+        for kanji in rows {
+            let kanji = kanji?;
+            found.insert(kanji.kanji, kanji);
+        }
+        Ok(found)
     }
     /*pub fn for_reading(c: &Connection, reading: u64) -> rusqlite::Result<Vec<Self>> {
         let mut s = c.prepare(Self::QUERY)?;
@@ -113,4 +407,190 @@ impl Kanji {
         }
         Ok(definitions)
     }*/
-}
\ No newline at end of file
+}
+
+/// The number of entries in the dictionary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DictionarySize {
+    pub words: i64,
+    pub definitions: i64,
+    pub kanji: i64
+}
+impl DictionarySize {
+    pub fn get(c: &Connection) -> Result<Self> {
+        Ok(c.prepare_cached("SELECT (SELECT COUNT(*) FROM `word`), (SELECT COUNT(*) FROM `definition`), (SELECT COUNT(*) FROM `kanji`)")?
+            .query_row([], |row| Ok(Self {
+                words: row.get(0)?,
+                definitions: row.get(1)?,
+                kanji: row.get(2)?
+            }))?
+        )
+    }
+}
+
+/// A list of `count` SQL parameter placeholders for use in an `IN` clause
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Insert `count` words named `word0`, `word1`..., each with `readings` readings of `definitions` definitions
+    fn insert_words(c: &Connection, count: usize, readings: usize, definitions: usize) -> Vec<String> {
+        let transaction = c.unchecked_transaction().unwrap();
+        let mut words = Vec::new();
+        for i in 0..count {
+            let word = format!("word{}", i);
+            let word_id = c.prepare_cached("INSERT INTO `word` (`word`) VALUES (?1)").unwrap()
+                .insert([&word]).unwrap();
+            for r in 0..readings {
+                let reading_id = c.prepare_cached("INSERT INTO `word_reading` (`word`, `full`, `accent`) VALUES (?1, ?2, '0')").unwrap()
+                    .insert(rusqlite::params!(word_id, format!("reading{}", r))).unwrap();
+                for d in 0..definitions {
+                    c.prepare_cached("INSERT INTO `definition` (`word_reading`, `definition`) VALUES (?1, ?2)").unwrap()
+                        .execute(rusqlite::params!(reading_id, format!("definition{}", d))).unwrap();
+                }
+            }
+            words.push(word);
+        }
+        transaction.commit().unwrap();
+        words
+    }
+
+    /// Insert a word with a single reading, or none if `reading` is `None`
+    fn insert_reading(c: &Connection, word: &str, reading: Option<&str>, tag: Option<&str>) {
+        let word_id = c.prepare_cached("INSERT INTO `word` (`word`) VALUES (?1)").unwrap()
+            .insert([word]).unwrap();
+        if let Some(reading) = reading {
+            c.prepare_cached("INSERT INTO `word_reading` (`word`, `full`, `accent`) VALUES (?1, ?2, '0')").unwrap()
+                .execute(rusqlite::params!(word_id, reading)).unwrap();
+        }
+        if let Some(tag) = tag {
+            let tag_id = data::Tag { tag: tag.into() }.get_or_insert(c).unwrap();
+            c.prepare_cached("INSERT INTO `word_tag` (`word`, `tag`) VALUES (?1, ?2)").unwrap()
+                .execute([word_id, tag_id]).unwrap();
+        }
+    }
+    /// Every word listed, following the cursors `limit` words at a time
+    fn list(c: &Connection, order: WordOrder, tag: Option<&str>, limit: u32) -> Vec<Vec<String>> {
+        let mut pages = Vec::new();
+        let mut after = None;
+        loop {
+            let page = WordSummary::page(c, order, tag, after.as_deref(), limit).unwrap();
+            pages.push(page.words.into_iter().map(|summary| summary.word).collect());
+            match page.next {
+                Some(next) => after = Some(next),
+                None => return pages
+            }
+        }
+    }
+
+    #[test]
+    fn pages_by_reading() {
+        let pool = data::memory();
+        let c = pool.get().unwrap();
+        insert_reading(&c, "b", Some("か:き"), Some("common"));
+        insert_reading(&c, "none", None, None);
+        insert_reading(&c, "empty", Some(""), Some("common"));
+        insert_reading(&c, "a", Some("か"), None);
+        insert_reading(&c, "c", Some("か:き"), Some("common"));
+        insert_reading(&c, "d", Some("さ"), None);
+
+        // Words without a reading sort as an empty reading, then by when they were added
+        assert_eq!(list(&c, WordOrder::Reading, None, 2), [
+            vec!["none", "empty"],
+            vec!["a", "b"],
+            vec!["c", "d"]
+        ]);
+        assert_eq!(list(&c, WordOrder::Reading, None, 4), [
+            vec!["none", "empty", "a", "b"],
+            vec!["c", "d"]
+        ]);
+        assert_eq!(list(&c, WordOrder::Reading, Some("common"), 1), [vec!["empty"], vec!["b"], vec!["c"]]);
+        assert_eq!(list(&c, WordOrder::Reading, Some("missing"), 1), [Vec::<String>::new()]);
+    }
+
+    #[test]
+    fn pages_by_recent() {
+        let pool = data::memory();
+        let c = pool.get().unwrap();
+        for (word, tag) in [("a", None), ("b", Some("common")), ("c", Some("common")), ("d", None)] {
+            insert_reading(&c, word, Some(word), tag);
+        }
+        assert_eq!(list(&c, WordOrder::Recent, None, 3), [vec!["d", "c", "b"], vec!["a"]]);
+        assert_eq!(list(&c, WordOrder::Recent, Some("common"), 1), [vec!["c"], vec!["b"]]);
+        // The last page is exactly full, so no cursor follows it
+        assert_eq!(list(&c, WordOrder::Recent, None, 4), [vec!["d", "c", "b", "a"]]);
+    }
+
+    #[test]
+    fn cursors_survive_readings_with_separators() {
+        let cursor = Cursor { id: 12, reading: Some("か:き:".into()) }.encode();
+        let decoded = Cursor::decode(&cursor).unwrap();
+        assert_eq!((decoded.id, decoded.reading.as_deref()), (12, Some("か:き:")));
+        let decoded = Cursor::decode(&Cursor { id: 3, reading: Some(String::new()) }.encode()).unwrap();
+        assert_eq!((decoded.id, decoded.reading.as_deref()), (3, Some("")));
+        assert!(matches!(Cursor::decode("not a cursor"), Err(Error::Cursor)));
+    }
+
+    #[test]
+    fn updates_move_to_the_next_revision() {
+        let pool = data::memory();
+        let c = pool.get().unwrap();
+        let word = |definition: &str| Word {
+            word: "単語".into(),
+            readings: vec![WordReading {
+                full: "たんご".into(),
+                accent: "0".into(),
+                definitions: vec![Definition { definition: definition.into() }]
+            }],
+            tags: vec![data::Tag { tag: "common".into() }],
+            revision: Revision::default()
+        };
+        word("word").insert(&c).unwrap();
+        let first = Word::get(&c, "単語").unwrap().unwrap();
+        assert_eq!(first.revision.revision, 1);
+        assert_eq!(first.readings[0].definitions[0].definition, "word");
+        assert!(matches!(word("word").insert(&c), Err(Error::Exists(_))));
+
+        assert!(word("vocabulary").update(&c).unwrap());
+        let second = Word::get(&c, "単語").unwrap().unwrap();
+        assert_eq!(second.revision.revision, 2);
+        assert_ne!(second.revision.tag(), first.revision.tag());
+        assert_eq!((second.readings.len(), second.tags.len()), (1, 1));
+        assert_eq!(second.readings[0].definitions[0].definition, "vocabulary");
+        let definitions: i64 = c.query_row("SELECT COUNT(*) FROM `definition`", [], |row| row.get(0)).unwrap();
+        assert_eq!(definitions, 1);
+
+        assert!(!Word { word: "言葉".into(), ..word("word") }.update(&c).unwrap());
+    }
+
+    #[test]
+    fn get_many_matches_get() {
+        const WORDS: usize = 2000;
+        let pool = data::memory();
+        let c = pool.get().unwrap();
+        let words = insert_words(&c, WORDS, 3, 4);
+        let batch = &words[..256];
+
+        let one_by_one: Vec<_> = batch.iter()
+            .map(|word| Word::get(&c, word).unwrap().unwrap())
+            .collect();
+        let many = Word::get_many(&c, batch).unwrap();
+
+        assert_eq!(many.len(), batch.len());
+        for word in one_by_one {
+            let other = &many[&word.word];
+            assert_eq!(other.revision.id, word.revision.id);
+            assert_eq!(other.readings.len(), 3);
+            for (reading, other) in word.readings.iter().zip(&other.readings) {
+                assert_eq!(reading.full, other.full);
+                let definitions = |reading: &WordReading| reading.definitions.iter().map(ToString::to_string).collect::<Vec<_>>();
+                assert_eq!(definitions(reading), definitions(other));
+            }
+        }
+        assert!(Word::get_many(&c, &["missing".to_string()]).unwrap().is_empty());
+    }
+}
@@ -6,14 +6,12 @@ mod prelude {
     pub use r2d2_sqlite::SqliteConnectionManager;
     pub use rusqlite::OptionalExtension;
     pub use serde::{ Serialize, Deserialize };
+    pub use utoipa::ToSchema;
 
     pub type Connection = r2d2::PooledConnection<SqliteConnectionManager>;
 
     pub mod serde_base64 {
-        use serde::{Serializer, Deserializer, Deserialize};
-        pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
-            serializer.serialize_str(&base64::encode(data))
-        }
+        use serde::{Deserializer, Deserialize};
         pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
             let raw = <&str>::deserialize(deserializer)?;
             base64::decode(raw).map_err(|_| serde::de::Error::invalid_value(serde::de::Unexpected::Str(raw), &"base64 encoded data"))
@@ -21,9 +19,28 @@ mod prelude {
     }
 }
 
+pub use prelude::Connection;
+
 pub mod auth;
 pub use auth::*;
 pub mod dictionary;
 pub use dictionary::*;
+pub mod schema;
+pub use schema::*;
+pub mod session;
+pub use session::*;
 pub mod tag;
-pub use tag::*;
\ No newline at end of file
+pub use tag::*;
+/// An in-memory database with the latest schema, for tests
+#[cfg(test)]
+pub(crate) fn memory() -> crate::prelude::Pool {
+    let manager = prelude::SqliteConnectionManager::memory()
+        .with_init(|c| c.execute_batch("PRAGMA foreign_keys = ON"));
+    // Every connection would open a separate database
+    let pool = r2d2::Pool::builder()
+        .max_size(1)
+        .build(manager)
+        .unwrap();
+    migrate(&pool.get().unwrap()).unwrap();
+    pool
+}
@@ -0,0 +1,172 @@
+use super::prelude::*;
+
+/// Creates the schema of the latest version in an empty database
+const SCHEMA: &str = include_str!("../schema.sql");
+
+/// Upgrades from the schema of each version to the next, where index 0 upgrades the original schema.
+///
+/// A step must never change once released, as databases may already have run it.
+/// `schema.sql` must always create the same schema as running every step.
+const MIGRATIONS: &[&str] = &[
+    include_str!("../migrations/01_indexes.sql"),
+    include_str!("../migrations/02_revisions.sql"),
+    include_str!("../migrations/03_rowids.sql"),
+    include_str!("../migrations/04_sessions.sql"),
+    include_str!("../migrations/05_algorithms.sql"),
+    include_str!("../migrations/06_challenge_scopes.sql")
+];
+
+/// The version of the latest schema, stored in the `user_version` of the database
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+/// Get the schema version of the database
+pub fn schema_version(c: &rusqlite::Connection) -> Result<i64> {
+    Ok(c.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+/// What `migrate` did
+#[derive(Debug, PartialEq, Eq)]
+pub enum Migration {
+    /// The schema was already at the version
+    Current(i64),
+    /// The database was empty, so the latest schema was created
+    Created(i64),
+    /// The schema was upgraded, after which `violations` rows failed the foreign key check
+    Upgraded {
+        from: i64,
+        to: i64,
+        violations: usize
+    }
+}
+
+/// Bring the schema up to date, creating it in an empty database.
+///
+/// Every step runs in a single transaction, so a failed migration leaves the database as it was.
+pub fn migrate(c: &rusqlite::Connection) -> Result<Migration> {
+    let version = schema_version(c)?;
+    if version > SCHEMA_VERSION {
+        return Err(Error::Other(format!(
+            "The database has schema version {}, which is newer than this release's version {}",
+            version,
+            SCHEMA_VERSION
+        ).into()))
+    }
+    let tables: i64 = c.query_row("SELECT COUNT(*) FROM `sqlite_master` WHERE `type` = 'table'", [], |row| row.get(0))?;
+    if tables > 0 && version == SCHEMA_VERSION {
+        return Ok(Migration::Current(version))
+    }
+
+    // Tables are rebuilt by some steps, which would break references midway.
+    // Enforcement cannot change within a transaction, so it is turned off around it.
+    let foreign_keys: bool = c.query_row("PRAGMA foreign_keys", [], |row| row.get(0))?;
+    c.execute_batch("PRAGMA foreign_keys = OFF")?;
+    let migrated = run(c, tables, version);
+    c.execute_batch(&format!("PRAGMA foreign_keys = {}", if foreign_keys { "ON" } else { "OFF" }))?;
+    migrated
+}
+
+fn run(c: &rusqlite::Connection, tables: i64, version: i64) -> Result<Migration> {
+    let transaction = c.unchecked_transaction()?;
+    if tables == 0 {
+        transaction.execute_batch(SCHEMA)?;
+    } else {
+        for step in &MIGRATIONS[version as usize..] {
+            transaction.execute_batch(step)?;
+        }
+    }
+    transaction.execute_batch(&format!("PRAGMA user_version = {}", SCHEMA_VERSION))?;
+    transaction.commit()?;
+
+    if tables == 0 {
+        return Ok(Migration::Created(SCHEMA_VERSION))
+    }
+    let violations = c.prepare("PRAGMA foreign_key_check")?
+        .query_map([], |_| Ok(()))?
+        .count();
+    Ok(Migration::Upgraded {
+        from: version,
+        to: SCHEMA_VERSION,
+        violations
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A database with the schema from before it was versioned
+    fn original() -> crate::prelude::Pool {
+        let pool = r2d2::Pool::builder()
+            .max_size(1)
+            .build(SqliteConnectionManager::memory())
+            .unwrap();
+        pool.get().unwrap().execute_batch(include_str!("../migrations/original.sql")).unwrap();
+        pool
+    }
+    /// The columns, keys and indexes of every table
+    fn describe(c: &rusqlite::Connection) -> Vec<String> {
+        let strings = |sql: &str| -> Vec<String> {
+            c.prepare(sql).unwrap()
+                .query_map([], |row| row.get(0)).unwrap()
+                .collect::<rusqlite::Result<_>>().unwrap()
+        };
+        let mut description = Vec::new();
+        for table in strings("SELECT `name` FROM `sqlite_master` WHERE `type` = 'table' ORDER BY `name`") {
+            description.push(format!("table {}", table));
+            description.extend(strings(&format!(
+                "SELECT `name` || ' ' || `type` || ' pk=' || `pk` || ' default=' || IFNULL(`dflt_value`, '') FROM pragma_table_info('{}')",
+                table
+            )));
+            description.extend(strings(&format!(
+                "SELECT 'references ' || `from` || ' ' || `table` || '(' || `to` || ')' FROM pragma_foreign_key_list('{}') ORDER BY `from`",
+                table
+            )));
+        }
+        for index in strings("SELECT `sql` FROM `sqlite_master` WHERE `type` = 'index' ORDER BY `name`") {
+            description.push(index.split_whitespace().collect::<Vec<_>>().join(" "));
+        }
+        description
+    }
+
+    #[test]
+    fn migrations_reach_the_latest_schema() {
+        let latest = rusqlite::Connection::open_in_memory().unwrap();
+        assert_eq!(migrate(&latest).unwrap(), Migration::Created(SCHEMA_VERSION));
+        let pool = original();
+        let c = pool.get().unwrap();
+        assert_eq!(migrate(&c).unwrap(), Migration::Upgraded { from: 0, to: SCHEMA_VERSION, violations: 0 });
+
+        assert_eq!(describe(&c), describe(&latest));
+        assert_eq!(migrate(&c).unwrap(), Migration::Current(SCHEMA_VERSION));
+    }
+
+    #[test]
+    fn migrations_keep_the_data() {
+        let pool = original();
+        let c = pool.get().unwrap();
+        c.execute_batch("
+            INSERT INTO `user` (`name`, `privilege`, `pubkey`) VALUES ('admin', 1, '');
+            INSERT INTO `challenge` (`user`, `challenge`, `expires`) VALUES (1, 'challenge', '2100-01-01T00:00:00Z');
+            INSERT INTO `tag` (`tag`) VALUES ('common');
+            INSERT INTO `word` (`word`) VALUES ('単語');
+            INSERT INTO `word_tag` (`word`, `tag`) VALUES (1, 1);
+            INSERT INTO `word_reading` (`word`, `full`, `accent`) VALUES (1, 'たんご', '0');
+            INSERT INTO `definition` (`word_reading`, `definition`) VALUES (1, 'word');
+        ").unwrap();
+        assert!(matches!(migrate(&c).unwrap(), Migration::Upgraded { violations: 0, .. }));
+
+        let word = data::Word::get(&c, "単語").unwrap().expect("the word was lost");
+        assert_eq!(word.revision.revision, 1);
+        assert_eq!(word.tags.len(), 1);
+        assert_eq!(word.readings[0].definitions[0].to_string(), "word");
+        assert_eq!(data::Challenge::outstanding(&c, "admin").unwrap().0, 1);
+        assert_eq!(data::User::credentials(&c, "admin").unwrap().unwrap().2, data::Privilege::Admin);
+    }
+
+    #[test]
+    fn newer_schema_is_refused() {
+        let c = rusqlite::Connection::open_in_memory().unwrap();
+        c.execute_batch(&format!("CREATE TABLE `later` (`a`); PRAGMA user_version = {}", SCHEMA_VERSION + 1)).unwrap();
+        assert!(migrate(&c).is_err());
+    }
+}
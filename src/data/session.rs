@@ -0,0 +1,76 @@
+use super::prelude::*;
+use super::Privilege;
+
+/// A login that authenticates requests by a bearer token instead of a signature over each request
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct Session {
+    pub id: i64,
+    pub created: DateTime<Utc>,
+    pub expires: DateTime<Utc>
+}
+impl Session {
+    /// Start a new session for the user lasting `lifetime`, returning the token for it
+    #[tracing::instrument(level = "debug", skip(c))]
+    pub fn create(c: &Connection, user: &str, lifetime: chrono::Duration) -> Result<Token> {
+        let mut token = [0; 32];
+        openssl::rand::rand_bytes(&mut token)?;
+        let token = base64::encode(token);
+        let created = Utc::now();
+        let expires = created + lifetime;
+        let mut s = c.prepare("INSERT INTO `session` (`user`, `token`, `created`, `expires`) SELECT `user`.`rowid`, ?1 AS `token`, ?2 AS `created`, ?3 AS `expires` FROM `user` WHERE `user`.`name` = ?4")?;
+        if s.execute(rusqlite::params!(Self::hash(&token), created, expires, user))? != 1 {
+            return Err(Error::NoUser(user.to_string()))
+        }
+        Ok(Token {
+            token,
+            expires
+        })
+    }
+    /// Get the user, and their privilege, that holds the unexpired session for `token`
+    #[tracing::instrument(level = "debug", skip(c, token))]
+    pub fn authenticate(c: &Connection, token: &str) -> Result<Option<(String, Privilege)>> {
+        c.prepare_cached("SELECT `user`.`name`, `user`.`privilege` FROM `session` INNER JOIN `user` ON `session`.`user` = `user`.`rowid` WHERE `session`.`token` = ?1 AND `session`.`expires` >= ?2")?
+            .query_row(rusqlite::params!(Self::hash(token), Utc::now()), |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()
+            .map_err(|e| e.into())
+    }
+    /// End the session for `token`
+    /// 
+    /// Returns true if the session existed
+    pub fn revoke(c: &Connection, token: &str) -> Result<bool> {
+        let mut s = c.prepare("DELETE FROM `session` WHERE `session`.`token` = ?1")?;
+        Ok(s.execute([Self::hash(token)])? == 1)
+    }
+    /// Get the unexpired sessions of the user
+    pub fn for_user(c: &Connection, user: &str) -> Result<Vec<Self>> {
+        let mut s = c.prepare("SELECT `session`.`rowid`, `session`.`created`, `session`.`expires` FROM `session` INNER JOIN `user` ON `session`.`user` = `user`.`rowid` WHERE `user`.`name` = ?1 AND `session`.`expires` >= ?2 ORDER BY `session`.`created`")?;
+        let rows = s.query_map(rusqlite::params!(user, Utc::now()), |row| {
+                Ok(Self {
+                    id: row.get(0)?,
+                    created: row.get(1)?,
+                    expires: row.get(2)?
+                })
+            })?;
+        let mut sessions = Vec::new();
+        for session in rows {
+            sessions.push(session?)
+        }
+        Ok(sessions)
+    }
+    /// Remove expired sessions, returning how many there were
+    pub fn prune_old(c: &Connection) -> Result<usize> {
+        let mut s = c.prepare_cached("DELETE FROM `session` WHERE `session`.`expires` < ?1")?;
+        Ok(s.execute([Utc::now()])?)
+    }
+    /// Tokens are only stored hashed so that a leaked database cannot be used to take over sessions
+    fn hash(token: &str) -> String {
+        base64::encode(openssl::sha::sha256(token.as_bytes()))
+    }
+}
+
+/// A bearer token for a session, given to the client once when the session is created
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct Token {
+    pub token: String,
+    pub expires: DateTime<Utc>
+}
@@ -1,18 +1,18 @@
 use super::prelude::*;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Tag {
     pub tag: String,
 }
 impl Tag {
     /// Get the rowid of a given tag
     pub fn id(&self, c: &Connection) -> Result<i64> {
-        c.prepare("SELECT `rowid` FROM `tag` WHERE `tag`.`tag` = ?1")?
+        c.prepare_cached("SELECT `rowid` FROM `tag` WHERE `tag`.`tag` = ?1")?
             .query_row([&self.tag], |row| row.get::<_, i64>(0))
             .map_err(|e| e.into())
     }
     pub fn for_word(c: &Connection, word: u64) -> rusqlite::Result<Vec<Self>> {
-        let mut s = c.prepare("SELECT `tag`.`tag` FROM `word_tag` INNER JOIN `tag` ON `word_tag`.`tag` = `tag`.`rowid` WHERE `word_tag`.`word` = ?1")?;
+        let mut s = c.prepare_cached("SELECT `tag`.`tag` FROM `word_tag` INNER JOIN `tag` ON `word_tag`.`tag` = `tag`.`rowid` WHERE `word_tag`.`word` = ?1")?;
         let rows = s.query_map([word], |row| {
                 Ok(Self {
                     tag: row.get(0)?
@@ -1,40 +1,123 @@
 use std::fmt;
 
+use actix_web::{
+    http::{header::{self, ContentType}, StatusCode},
+    HttpResponse
+};
+
 pub type Result<T> = std::result::Result<T, Error>;
 #[derive(Debug)]
 pub enum Error {
+    Algorithm(crate::data::Algorithm),
     Blocking(actix_web::error::BlockingError),
+    /// The request body could not be deserialised
+    Body(serde_json::Error),
     Challenge,
     ConnectionPool(r2d2::Error),
+    Cursor,
     Exists(String),
+    Io(std::io::Error),
     Json(serde_json::Error),
+    KeyPolicy(String),
     NoUser(String),
     Other(Box<dyn 'static + std::error::Error + Send + Sync>),
+    /// The word in the path is not the word in the body
+    PathMismatch { path: String, body: String },
     Privilege,
+    /// Too many requests were made, try again after the duration
+    RateLimited(std::time::Duration),
+    Session,
     Signature,
     Sql(rusqlite::Error),
     Ssl(openssl::error::ErrorStack),
+    TooMany(usize),
     Utf8(std::str::Utf8Error),
 }
-impl actix_web::ResponseError for Error {}
+impl actix_web::ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Algorithm(_) | Self::Body(_) | Self::Cursor | Self::KeyPolicy(_) | Self::PathMismatch { .. } | Self::TooMany(_) => StatusCode::BAD_REQUEST,
+            Self::Challenge | Self::NoUser(_) | Self::Session | Self::Signature => StatusCode::UNAUTHORIZED,
+            Self::Privilege => StatusCode::FORBIDDEN,
+            Self::Exists(_) => StatusCode::CONFLICT,
+            Self::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            _ => StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+    fn error_response(&self) -> HttpResponse {
+        crate::metrics::error(self);
+        let mut response = HttpResponse::build(self.status_code());
+        if let Self::RateLimited(retry) = self {
+            // Round up so that the client does not retry too early
+            let seconds = retry.as_secs() + u64::from(retry.subsec_nanos() > 0);
+            response.insert_header((header::RETRY_AFTER, seconds.to_string()));
+        }
+        response
+            .content_type(ContentType::plaintext())
+            .body(self.to_string())
+    }
+}
+impl Error {
+    /// The name of the variant, for grouping errors in metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Algorithm(_) => "algorithm",
+            Self::Blocking(_) => "blocking",
+            Self::Body(_) => "body",
+            Self::Challenge => "challenge",
+            Self::ConnectionPool(_) => "connection_pool",
+            Self::Cursor => "cursor",
+            Self::Exists(_) => "exists",
+            Self::Io(_) => "io",
+            Self::Json(_) => "json",
+            Self::KeyPolicy(_) => "key_policy",
+            Self::NoUser(_) => "no_user",
+            Self::Other(_) => "other",
+            Self::PathMismatch { .. } => "path_mismatch",
+            Self::Privilege => "privilege",
+            Self::RateLimited(_) => "rate_limited",
+            Self::Session => "session",
+            Self::Signature => "signature",
+            Self::Sql(_) => "sql",
+            Self::Ssl(_) => "ssl",
+            Self::TooMany(_) => "too_many",
+            Self::Utf8(_) => "utf8"
+        }
+    }
+}
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            Self::Algorithm(algorithm) => write!(f, "[Authentication Error] Key cannot be used with the {} signature algorithm", algorithm.name()),
             Self::Blocking(e) => write!(f, "[Blocking Error] {}", e),
+            Self::Body(e) => write!(f, "[Request Error] Invalid request body: {}", e),
             Self::Challenge => write!(f, "[Authentication Error] Challenge expired or invalid"),
             Self::ConnectionPool(e) => write!(f, "[Connection Pool Error] {}", e),
+            Self::Cursor => write!(f, "[Request Error] Invalid page cursor"),
             Self::Exists(item) => write!(f, "[Request Error] {:?} already exists", item),
+            Self::Io(e) => write!(f, "[IO Error] {}", e),
             Self::Json(e) => write!(f, "[JSON Error] {}", e),
+            Self::KeyPolicy(reason) => write!(f, "[Request Error] Key rejected: {}", reason),
             Self::NoUser(user) => write!(f, "[Authentication Error] No user {:?}", user),
             Self::Other(e) => write!(f, "[Other Error] {}", e),
+            Self::PathMismatch { path, body } => write!(f, "[Request Error] The path is for {:?} but the body is for {:?}", path, body),
             Self::Privilege => write!(f, "[Authorization Error] You do not have the required privileges"),
+            Self::RateLimited(_) => write!(f, "[Request Error] Too many requests, try again later"),
+            Self::Session => write!(f, "[Authentication Error] Session expired or invalid"),
             Self::Signature => write!(f, "[Authentication Error] Payload signature is invalid"),
             Self::Sql(e) => write!(f, "[SQL Error] {}", e),
             Self::Ssl(e) => write!(f, "[OpenSSL Error] {}", e),
+            Self::TooMany(limit) => write!(f, "[Request Error] Too many items requested, at most {} are allowed", limit),
             Self::Utf8(e) => write!(f, "[UTF-8 Encoding Error] {}", e)
         }
     }
 }
+impl std::error::Error for Error {}
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
 impl From<openssl::error::ErrorStack> for Error {
     fn from(e: openssl::error::ErrorStack) -> Self {
         Self::Ssl(e)
@@ -0,0 +1,460 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    time::Duration
+};
+
+use actix_web::{
+    App,
+    get,
+    http::Method,
+    HttpResponse,
+    HttpServer,
+    middleware,
+    Responder,
+};
+use once_cell::sync::OnceCell;
+use r2d2_sqlite::SqliteConnectionManager;
+
+pub mod cache;
+mod cli;
+pub mod data;
+pub mod error;
+pub mod metrics;
+pub mod prune;
+mod settings;
+pub mod tls;
+pub mod web;
+
+use prelude::*;
+mod prelude {
+    pub use crate::{
+        cache,
+        data::{self, auth::{By, Signed}},
+        error::{Error, Result},
+    };
+    pub type Pool = r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>;
+}
+
+
+/// Loaded from the `otango` configuration, after which any field may be overridden by an environment
+/// variable named `OTANGO_` and the field in upper case, such as `OTANGO_DATABASE_POOL_SIZE`.
+/// Lists are separated by commas, and optional fields are unset by an empty variable.
+///
+/// `otango --print-config` prints every variable with its effective value.
+#[derive(Debug, config::Config)]
+struct Config {
+    /// The address to serve the API on
+    address: SocketAddr,
+    /// Serve HTTPS on `address`, or plain HTTP when false, such as behind a proxy that terminates TLS
+    tls: bool,
+    /// Redirect plain HTTP requests on this address to HTTPS, such as on port 80
+    redirect_address: Option<SocketAddr>,
+    /// IP addresses of proxies whose `X-Forwarded-For` and `X-Forwarded-Proto` headers are believed
+    trusted_proxies: Vec<String>,
+    /// Path to the PEM encoded private key for TLS
+    key: String,
+    /// Path to the PEM encoded certificate chain for TLS, starting with the server's certificate
+    cert: String,
+    /// Report as not ready once the certificate expires within this many days
+    cert_expiry_warning: u64,
+    /// Seconds between checking whether the key or certificate changed, or 0 to only reload on `SIGHUP`
+    cert_reload_interval: u64,
+    /// Path to the SQLite database, created by `otango migrate`
+    database: String,
+    /// Use write-ahead logging so that readers do not block writers
+    database_wal: bool,
+    /// Milliseconds to wait for a lock held by another connection before failing with `SQLITE_BUSY`
+    database_busy_timeout: u64,
+    /// Enforce foreign keys, which only happens once the database is migrated to the latest schema
+    database_foreign_keys: bool,
+    /// One of `OFF`, `NORMAL`, `FULL` or `EXTRA`
+    database_synchronous: String,
+    /// The maximum number of open database connections
+    database_pool_size: u32,
+    /// Seconds to wait for a free database connection
+    database_pool_timeout: u64,
+    /// Where `/` redirects to, such as the home page of a client, or a 404 response when unset
+    root_redirection: Option<String>,
+    /// Allow logging in for a session token instead of signing every request
+    sessions: bool,
+    /// Minutes until a session expires
+    session_lifetime: u64,
+    /// Signature algorithms that may be used by newly registered keys: `RS256`, `PS256`, `ES256` and `EdDSA`
+    allowed_algorithms: Vec<String>,
+    /// The smallest RSA key, in bits, that may be registered
+    min_rsa_bits: u32,
+    /// Registrations allowed per minute from each IP address, or 0 for no limit
+    register_rate_limit: u32,
+    /// Registrations allowed at once from each IP address before being limited
+    register_rate_burst: u32,
+    /// Challenges allowed per minute from each IP address, or 0 for no limit
+    challenge_rate_limit: u32,
+    /// Challenges allowed at once from each IP address before being limited
+    challenge_rate_burst: u32,
+    /// Challenges allowed per minute for each user, or 0 for no limit
+    challenge_user_rate_limit: u32,
+    /// Challenges allowed at once for each user before being limited
+    challenge_user_rate_burst: u32,
+    /// The most unexpired challenges a user may have, or 0 for no limit
+    max_outstanding_challenges: u32,
+    /// Seconds between removing expired challenges, sessions and rate limits, or 0 to never remove them
+    prune_interval: u64,
+    /// A tracing filter such as `info` or `info,otango::data=debug`, the latter showing the time taken by each query
+    log_level: String,
+    /// `text` or `json`
+    log_format: String,
+    /// Milliseconds after which a database call is logged as slow
+    slow_query_threshold: u64,
+    /// Serve `/metrics` over plain HTTP on this address only, instead of alongside the API
+    metrics_address: Option<SocketAddr>,
+    /// Origins allowed to make cross-origin requests, such as `https://example.com`, `https://*.example.com` for
+    /// any subdomain, or `*` for any origin. Cross-origin requests are denied when empty.
+    allowed_origins: Vec<String>,
+    /// Methods allowed in cross-origin requests
+    cors_methods: Vec<String>,
+    /// Request headers allowed in cross-origin requests
+    cors_headers: Vec<String>,
+    /// Response headers that cross-origin scripts may read
+    cors_expose_headers: Vec<String>,
+    /// Allow cross-origin requests to include cookies and authorization headers
+    cors_credentials: bool,
+    /// Seconds that browsers may cache the result of a preflight request, or 0 to not say
+    cors_max_age: usize,
+    /// Compress responses with gzip, brotli or zstd when the client accepts it
+    compression: bool,
+    /// Bytes below which responses are not compressed
+    compression_threshold: usize,
+    /// The number of words, and separately kanji, to keep cached in memory
+    cache_capacity: usize,
+    /// Seconds a cached entry is served before it is loaded again, bounding how long writes made outside of the server,
+    /// such as by `otango seed`, go unseen. 0 disables the cache.
+    cache_ttl: u64,
+    /// The `Cache-Control` header sent with dictionary entries, or empty to send none
+    cache_control: String
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address: SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 443)),
+            tls: true,
+            redirect_address: None,
+            trusted_proxies: Vec::new(),
+            key: "key.pem".into(),
+            cert: "cert.pem".into(),
+            cert_expiry_warning: 7,
+            cert_reload_interval: 60,
+            database: "お単語.db".into(),
+            database_wal: true,
+            database_busy_timeout: 5000,
+            database_foreign_keys: true,
+            database_synchronous: "NORMAL".into(),
+            database_pool_size: 10,
+            database_pool_timeout: 30,
+            root_redirection: None,
+            sessions: true,
+            session_lifetime: 60,
+            allowed_algorithms: data::Algorithm::ALL.iter().map(|algorithm| algorithm.name().into()).collect(),
+            min_rsa_bits: 2048,
+            register_rate_limit: 6,
+            register_rate_burst: 3,
+            challenge_rate_limit: 60,
+            challenge_rate_burst: 20,
+            challenge_user_rate_limit: 30,
+            challenge_user_rate_burst: 10,
+            max_outstanding_challenges: 20,
+            prune_interval: 300,
+            log_level: "info".into(),
+            log_format: "text".into(),
+            slow_query_threshold: 250,
+            metrics_address: None,
+            allowed_origins: vec![],
+            cors_methods: vec!["GET".into(), "POST".into()],
+            cors_headers: vec!["Authorization".into(), "Content-Type".into(), "If-Modified-Since".into(), "If-None-Match".into()],
+            cors_expose_headers: vec!["ETag".into(), "Last-Modified".into(), "Retry-After".into()],
+            cors_credentials: false,
+            cors_max_age: 3600,
+            compression: true,
+            compression_threshold: 1024,
+            cache_capacity: 1024,
+            cache_ttl: 60,
+            cache_control: "public, max-age=300".into()
+        }
+    }
+}
+
+impl Config {
+    /// Apply the database settings to a newly opened connection
+    fn init_connection(&self, c: &mut rusqlite::Connection) -> rusqlite::Result<()> {
+        c.busy_timeout(Duration::from_millis(self.database_busy_timeout))?;
+        if self.database_wal {
+            c.execute_batch("PRAGMA journal_mode = WAL")?;
+        }
+        let synchronous = match self.database_synchronous.to_uppercase().as_str() {
+            synchronous @ ("OFF" | "NORMAL" | "FULL" | "EXTRA") => synchronous.to_string(),
+            _ => return Err(rusqlite::Error::InvalidParameterName(format!("synchronous = {}", self.database_synchronous)))
+        };
+        // Older schemas reference tables without a declared key, which fails every write once enforced
+        let migrated = c.query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0))? >= data::SCHEMA_VERSION;
+        c.execute_batch(&format!(
+            "PRAGMA foreign_keys = {}; PRAGMA synchronous = {};",
+            if self.database_foreign_keys && migrated { "ON" } else { "OFF" },
+            synchronous
+        ))
+    }
+    /// Open a pool of connections to the database, initialised for this configuration
+    fn pool(&'static self, size: u32) -> std::result::Result<Pool, r2d2::Error> {
+        let manager = SqliteConnectionManager::file(&self.database)
+            .with_init(move |c| self.init_connection(c));
+        Pool::builder()
+            .max_size(size)
+            .connection_timeout(Duration::from_secs(self.database_pool_timeout))
+            .build(manager)
+    }
+    fn init_logging(&self) {
+        use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
+        let filter = EnvFilter::try_new(&self.log_level)
+            .unwrap_or_else(|_| EnvFilter::new("info"));
+        // Logging when spans close shows how long each traced call took.
+        // Logs go to stderr so that they never mix with the output of commands.
+        let subscriber = tracing_subscriber::fmt()
+            .with_env_filter(filter)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_writer(std::io::stderr);
+        if self.log_format == "json" {
+            subscriber.json().init();
+        } else {
+            subscriber.init();
+        }
+    }
+    /// The keys that may be registered. Unknown algorithm names are ignored.
+    fn key_policy(&self) -> data::KeyPolicy {
+        data::KeyPolicy {
+            algorithms: self.allowed_algorithms.iter()
+                .filter_map(|algorithm| algorithm.parse().ok())
+                .collect(),
+            min_rsa_bits: self.min_rsa_bits
+        }
+    }
+}
+
+static CONFIG: OnceCell<Config> = OnceCell::new();
+
+/// Run the command given on the command line, serving the API by default
+pub async fn run() -> std::io::Result<()> {
+    let command = match cli::Command::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, cli::USAGE);
+            std::process::exit(2)
+        }
+    };
+    let mut config = <Config as config::Config>::load("otango");
+    let mut problems = config.apply_env();
+    problems.extend(config.validate());
+    if let cli::Command::PrintConfig = command {
+        config.print();
+    }
+    if !problems.is_empty() && command.validates() {
+        eprintln!("The configuration is invalid:");
+        for problem in &problems {
+            eprintln!("    {}", problem);
+        }
+        std::process::exit(2)
+    }
+    let config = CONFIG.get_or_init(|| config);
+    config.init_logging();
+    for problem in &problems {
+        tracing::warn!(%problem, "invalid configuration");
+    }
+
+    match command {
+        cli::Command::Serve => serve(config).await,
+        command => {
+            if let Err(error) = cli::run(command, config) {
+                eprintln!("{}", error);
+                std::process::exit(1)
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn serve(config: &'static Config) -> std::io::Result<()> {
+    let ssl = if config.tls {
+        let tls = tls::Reloadable::load(&config.key, &config.cert)
+            .map_err(std::io::Error::other)?;
+        let ssl = tls.acceptor()
+            .map_err(std::io::Error::other)?;
+        #[cfg(unix)]
+        actix_web::rt::spawn(tls::reload_on_hangup(tls.clone()));
+        if config.cert_reload_interval > 0 {
+            actix_web::rt::spawn(tls::watch(tls, Duration::from_secs(config.cert_reload_interval)));
+        }
+        Some(ssl)
+    } else {
+        None
+    };
+
+    let pool = config.pool(config.database_pool_size)
+        .map_err(std::io::Error::other)?;
+    let cache = actix_web::web::Data::new(cache::Dictionary::new(config.cache_capacity, Duration::from_secs(config.cache_ttl)));
+    let rate_limit = web::limit::RateLimit::new()
+        .route(Method::POST, "/auth/register", web::limit::Limiter::new(config.register_rate_limit, config.register_rate_burst))
+        .route(Method::POST, "/auth/challenge", web::limit::Limiter::new(config.challenge_rate_limit, config.challenge_rate_burst));
+    let user_challenge_limit = actix_web::web::Data::new(web::limit::Limiter::<String>::new(config.challenge_user_rate_limit, config.challenge_user_rate_burst));
+
+    let pruned = actix_web::web::Data::new(prune::Pruned::default());
+    if config.prune_interval > 0 {
+        let prunable = prune::Prunable {
+            pool: pool.clone(),
+            rate_limit: rate_limit.clone(),
+            user_challenge_limit: user_challenge_limit.clone()
+        };
+        actix_web::rt::spawn(prune::run(prunable, pruned.clone(), Duration::from_secs(config.prune_interval)));
+    }
+
+    if let Some(address) = config.metrics_address {
+        let pool = pool.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(actix_web::web::Data::new(pool.clone()))
+                .service(web::metrics::metrics)
+        }).workers(1)
+            .bind(address)?
+            .run();
+        tracing::info!(%address, "serving metrics");
+        actix_web::rt::spawn(server);
+    }
+
+    if let Some(address) = config.redirect_address {
+        let server = HttpServer::new(|| {
+            App::new()
+                .default_service(actix_web::web::to(web::proxy::to_https))
+        }).workers(1)
+            .bind(address)?
+            .run();
+        tracing::info!(%address, "redirecting to HTTPS");
+        actix_web::rt::spawn(server);
+    }
+
+    let server = HttpServer::new(move || {
+        App::new()
+    //        .app_data(web::Data::new(auth::ChallengeStore::default()))
+            .app_data(actix_web::web::Data::new(pool.clone()))
+            .app_data(cache.clone())
+            .app_data(user_challenge_limit.clone())
+            .app_data(pruned.clone())
+            .wrap(middleware::Condition::new(config.compression, web::compress::Threshold(config.compression_threshold)))
+            .wrap(middleware::Condition::new(config.compression, middleware::Compress::default()))
+            .wrap(rate_limit.clone())
+            .wrap(web::log::RequestLog)
+            .wrap(web::cors::cors())
+
+            .configure(|cfg| routes(cfg, config.metrics_address.is_none()))
+    });
+    let server = match ssl {
+        Some(ssl) => server.bind_openssl(config.address, ssl)?,
+        None => server.bind(config.address)?
+    };
+    tracing::info!(address = %config.address, tls = config.tls, "listening");
+    server.run().await
+}
+
+/// Register every route of the API server, including `/metrics` unless it is served separately.
+/// The OpenAPI document is built from the same routes.
+fn routes<R: web::Routes>(cfg: &mut R, metrics: bool) {
+    cfg.route(index)
+        .route(web::health::health)
+        .route(web::health::ready)
+        .route(web::stats::stats)
+        .route(web::openapi::openapi);
+    if metrics {
+        cfg.route(web::metrics::metrics);
+    }
+    //cfg.service(backup);
+    web::api(cfg);
+}
+
+/// Redirect to the configured home page
+#[utoipa::path(responses(
+    (status = 308, description = "The home page, in the `Location` header"),
+    (status = 404, description = "No home page is configured")
+))]
+#[get("/")]
+async fn index() -> impl Responder {
+    if let Some(location) = CONFIG.get().and_then(|c| c.root_redirection.as_ref()) {
+        HttpResponse::PermanentRedirect()
+            .append_header(("Location", location.as_str()))
+            .finish()
+    } else {
+        HttpResponse::NotFound()
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The default configuration, using a database in `directory`
+    pub(crate) fn config(directory: &tempfile::TempDir) -> &'static Config {
+        Box::leak(Box::new(Config {
+            database: directory.path().join("test.db").to_string_lossy().into_owned(),
+            ..Config::default()
+        }))
+    }
+    fn word(word: String) -> data::Word {
+        data::Word {
+            word,
+            readings: Vec::new(),
+            tags: vec![data::Tag { tag: "common".into() }],
+            revision: Default::default()
+        }
+    }
+
+    #[test]
+    fn concurrent_writers_wait_for_the_lock() {
+        let directory = tempfile::tempdir().unwrap();
+        let pool = config(&directory).pool(8).unwrap();
+        data::migrate(&pool.get().unwrap()).unwrap();
+
+        let writers: Vec<_> = (0..8).map(|writer| {
+            let pool = pool.clone();
+            std::thread::spawn(move || for i in 0..25 {
+                word(format!("{}-{}", writer, i)).insert(&pool.get().unwrap()).unwrap();
+            })
+        }).collect();
+        for writer in writers {
+            writer.join().expect("a write failed");
+        }
+
+        let c = pool.get().unwrap();
+        let words: i64 = c.query_row("SELECT COUNT(*) FROM `word`", [], |row| row.get(0)).unwrap();
+        let tags: i64 = c.query_row("SELECT COUNT(*) FROM `tag`", [], |row| row.get(0)).unwrap();
+        assert_eq!((words, tags), (200, 1));
+    }
+
+    #[test]
+    fn foreign_keys_are_enforced_once_migrated() {
+        let directory = tempfile::tempdir().unwrap();
+        let config = config(&directory);
+        rusqlite::Connection::open(&config.database).unwrap()
+            .execute_batch(include_str!("migrations/original.sql")).unwrap();
+
+        // Enforcing them on the original schema would fail with a foreign key mismatch
+        let c = config.pool(1).unwrap().get().unwrap();
+        c.execute_batch("
+            INSERT INTO `word` (`word`) VALUES ('単語');
+            INSERT INTO `tag` (`tag`) VALUES ('common');
+            INSERT INTO `word_tag` (`word`, `tag`) VALUES (1, 1), (1000, 1);
+        ").unwrap();
+        let migration = data::migrate(&c).unwrap();
+        assert_eq!(migration, data::Migration::Upgraded { from: 0, to: data::SCHEMA_VERSION, violations: 1 });
+        drop(c);
+
+        let c = config.pool(1).unwrap().get().unwrap();
+        word("言葉".into()).insert(&c).unwrap();
+        let orphan = c.execute("INSERT INTO `word_tag` (`word`, `tag`) VALUES (1000, 1)", []);
+        assert!(orphan.is_err(), "a tag was added to a word that does not exist");
+    }
+}
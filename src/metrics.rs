@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use prometheus::{
+    core::Collector,
+    Encoder,
+    histogram_opts,
+    HistogramVec,
+    IntCounterVec,
+    IntGauge,
+    IntGaugeVec,
+    opts,
+    Registry,
+    TextEncoder
+};
+
+use crate::prelude::*;
+
+static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
+
+static REQUESTS: Lazy<HistogramVec> = Lazy::new(|| register(HistogramVec::new(
+    histogram_opts!("otango_request_duration_seconds", "Time taken to respond to requests"),
+    &["method", "route", "status"]
+)));
+static ERRORS: Lazy<IntCounterVec> = Lazy::new(|| register(IntCounterVec::new(
+    opts!("otango_errors_total", "Errors responded with, by kind"),
+    &["kind"]
+)));
+static CHALLENGES: Lazy<IntCounterVec> = Lazy::new(|| register(IntCounterVec::new(
+    opts!("otango_challenges_total", "Challenges issued, verified and rejected"),
+    &["outcome"]
+)));
+static PRUNED: Lazy<IntCounterVec> = Lazy::new(|| register(IntCounterVec::new(
+    opts!("otango_pruned_total", "Expired records removed by pruning, by kind"),
+    &["kind"]
+)));
+static POOL_CONNECTIONS: Lazy<IntGaugeVec> = Lazy::new(|| register(IntGaugeVec::new(
+    opts!("otango_pool_connections", "Open database connections, by whether they are in use"),
+    &["state"]
+)));
+static POOL_MAX: Lazy<IntGauge> = Lazy::new(|| register(IntGauge::with_opts(
+    opts!("otango_pool_max_connections", "The most database connections that may be open")
+)));
+static DICTIONARY: Lazy<IntGaugeVec> = Lazy::new(|| register(IntGaugeVec::new(
+    opts!("otango_dictionary_entries", "Entries in the dictionary, by kind"),
+    &["kind"]
+)));
+
+fn register<T: 'static + Collector + Clone>(collector: prometheus::Result<T>) -> T {
+    let collector = collector.expect("Invalid metric");
+    REGISTRY.register(Box::new(collector.clone()))
+        .expect("Metric registered twice");
+    collector
+}
+
+pub fn request(method: &str, route: &str, status: u16, latency: Duration) {
+    REQUESTS.with_label_values(&[method, route, &status.to_string()])
+        .observe(latency.as_secs_f64());
+}
+pub fn error(error: &Error) {
+    ERRORS.with_label_values(&[error.kind()])
+        .inc();
+}
+pub fn challenge(outcome: &str) {
+    CHALLENGES.with_label_values(&[outcome])
+        .inc();
+}
+pub fn pruned(kind: &str, count: usize) {
+    PRUNED.with_label_values(&[kind])
+        .inc_by(count as u64);
+}
+
+/// Encode every metric in the Prometheus text format, first updating those that are sampled
+pub fn encode(pool: &Pool, size: &data::DictionarySize) -> Result<Vec<u8>> {
+    let state = pool.state();
+    POOL_CONNECTIONS.with_label_values(&["idle"]).set(state.idle_connections as i64);
+    POOL_CONNECTIONS.with_label_values(&["active"]).set((state.connections - state.idle_connections) as i64);
+    POOL_MAX.set(pool.max_size() as i64);
+    DICTIONARY.with_label_values(&["word"]).set(size.words);
+    DICTIONARY.with_label_values(&["definition"]).set(size.definitions);
+    DICTIONARY.with_label_values(&["kanji"]).set(size.kanji);
+
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&REGISTRY.gather(), &mut buffer)
+        .map_err(|e| Error::Other(Box::new(e)))?;
+    Ok(buffer)
+}
@@ -0,0 +1,7 @@
+/* Index the columns joined on when getting words */
+CREATE INDEX `idx_word_tag`
+    ON `word_tag`(`word`);
+CREATE INDEX `idx_word_reading`
+    ON `word_reading`(`word`);
+CREATE INDEX `idx_definition`
+    ON `definition`(`word_reading`);
@@ -0,0 +1,5 @@
+/* Existing entries start at the first revision, with an unknown modification time */
+ALTER TABLE `word` ADD COLUMN `revision` INTEGER DEFAULT 1;
+ALTER TABLE `word` ADD COLUMN `modified` DATETIME;
+ALTER TABLE `kanji` ADD COLUMN `revision` INTEGER DEFAULT 1;
+ALTER TABLE `kanji` ADD COLUMN `modified` DATETIME;
@@ -0,0 +1,81 @@
+/*
+ * Foreign keys must reference a declared key, so referenced tables are rebuilt to alias the rowid.
+ * Copying the rowid keeps every reference to the rows intact.
+ */
+CREATE TABLE `user_new` (
+    `rowid`         INTEGER PRIMARY KEY,
+    `name`          TEXT,
+    `contact`       TEXT,
+    `image`         BLOB,
+    `privilege`     INTEGER,
+    `pubkey`        TEXT
+);
+INSERT INTO `user_new` (`rowid`, `name`, `contact`, `image`, `privilege`, `pubkey`)
+    SELECT `rowid`, `name`, `contact`, `image`, `privilege`, `pubkey` FROM `user`;
+DROP TABLE `user`;
+ALTER TABLE `user_new` RENAME TO `user`;
+CREATE UNIQUE INDEX `idx_user_name`
+    ON `user`(`name`);
+
+CREATE TABLE `tag_new` (
+    `rowid`         INTEGER PRIMARY KEY,
+    `tag`           TEXT
+);
+INSERT INTO `tag_new` (`rowid`, `tag`)
+    SELECT `rowid`, `tag` FROM `tag`;
+DROP TABLE `tag`;
+ALTER TABLE `tag_new` RENAME TO `tag`;
+CREATE UNIQUE INDEX `idx_tag`
+    ON `tag`(`tag`);
+
+CREATE TABLE `word_new` (
+    `rowid`         INTEGER PRIMARY KEY,
+    `word`          TEXT,
+    `revision`      INTEGER DEFAULT 1,
+    `modified`      DATETIME
+);
+INSERT INTO `word_new` (`rowid`, `word`, `revision`, `modified`)
+    SELECT `rowid`, `word`, `revision`, `modified` FROM `word`;
+DROP TABLE `word`;
+ALTER TABLE `word_new` RENAME TO `word`;
+CREATE UNIQUE INDEX `idx_word`
+    ON `word`(`word`);
+
+CREATE TABLE `word_reading_new` (
+    `rowid`         INTEGER PRIMARY KEY,
+    `word`          INTEGER,
+    `full`          TEXT,
+    `accent`        TEXT,
+    FOREIGN KEY (`word`)            REFERENCES `word`(`rowid`)
+);
+INSERT INTO `word_reading_new` (`rowid`, `word`, `full`, `accent`)
+    SELECT `rowid`, `word`, `full`, `accent` FROM `word_reading`;
+DROP TABLE `word_reading`;
+ALTER TABLE `word_reading_new` RENAME TO `word_reading`;
+CREATE INDEX `idx_word_reading`
+    ON `word_reading`(`word`);
+
+CREATE TABLE `kanji_new` (
+    `rowid`         INTEGER PRIMARY KEY,
+    `kanji`         CHAR(1),
+    `memonic`       TEXT,
+    `revision`      INTEGER DEFAULT 1,
+    `modified`      DATETIME
+);
+INSERT INTO `kanji_new` (`rowid`, `kanji`, `memonic`, `revision`, `modified`)
+    SELECT `rowid`, `kanji`, `memonic`, `revision`, `modified` FROM `kanji`;
+DROP TABLE `kanji`;
+ALTER TABLE `kanji_new` RENAME TO `kanji`;
+CREATE UNIQUE INDEX `idx_kanji`
+    ON `kanji`(`kanji`);
+
+CREATE TABLE `kanji_reading_new` (
+    `rowid`         INTEGER PRIMARY KEY,
+    `kanji`         INTEGER,
+    `memonic`       TEXT,
+    FOREIGN KEY (`kanji`)           REFERENCES `kanji`(`rowid`)
+);
+INSERT INTO `kanji_reading_new` (`rowid`, `kanji`, `memonic`)
+    SELECT `rowid`, `kanji`, `memonic` FROM `kanji_reading`;
+DROP TABLE `kanji_reading`;
+ALTER TABLE `kanji_reading_new` RENAME TO `kanji_reading`;
@@ -0,0 +1,10 @@
+CREATE TABLE `session` (
+    `user`          INTEGER,
+    /* SHA-256 hash of the bearer token */
+    `token`         TEXT,
+    `created`       DATETIME,
+    `expires`       DATETIME,
+    FOREIGN KEY (`user`)            REFERENCES `user`(`rowid`)
+);
+CREATE UNIQUE INDEX `idx_session`
+    ON `session`(`token`);
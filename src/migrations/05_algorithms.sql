@@ -0,0 +1,2 @@
+/* The signature algorithm name, where NULL is inferred from the kind of key */
+ALTER TABLE `user` ADD COLUMN `algorithm` TEXT;
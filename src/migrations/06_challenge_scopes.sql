@@ -0,0 +1,4 @@
+/* The request the challenge may be used for, where NULL allows any, as outstanding challenges did */
+ALTER TABLE `challenge` ADD COLUMN `method` TEXT;
+ALTER TABLE `challenge` ADD COLUMN `path` TEXT;
+ALTER TABLE `challenge` ADD COLUMN `purpose` TEXT;
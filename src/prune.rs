@@ -0,0 +1,75 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration
+};
+
+use actix_web::web::{self, Data};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    prelude::*,
+    web::limit::{Limiter, RateLimit}
+};
+
+/// Counts of what the background pruning task has removed
+#[derive(Default)]
+pub struct Pruned {
+    runs: AtomicU64,
+    challenges: AtomicU64,
+    sessions: AtomicU64
+}
+impl Pruned {
+    pub fn stats(&self) -> PrunedStats {
+        PrunedStats {
+            runs: self.runs.load(Ordering::Relaxed),
+            challenges: self.challenges.load(Ordering::Relaxed),
+            sessions: self.sessions.load(Ordering::Relaxed)
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PrunedStats {
+    pub runs: u64,
+    pub challenges: u64,
+    pub sessions: u64
+}
+
+/// Everything that expires and must be cleaned up
+pub struct Prunable {
+    pub pool: Pool,
+    pub rate_limit: RateLimit,
+    pub user_challenge_limit: Data<Limiter<String>>
+}
+
+/// Remove expired records every `interval`, forever
+pub async fn run(prunable: Prunable, pruned: Data<Pruned>, interval: Duration) {
+    let prunable = Data::new(prunable);
+    let mut interval = actix_web::rt::time::interval(interval);
+    loop {
+        interval.tick().await;
+        let prunable = prunable.clone();
+        let pruned = pruned.clone();
+        let result = web::block(move || -> Result<()> {
+            prunable.rate_limit.prune();
+            prunable.user_challenge_limit.prune();
+
+            let c = prunable.pool.get()?;
+            let challenges = data::Challenge::prune_old(&c)?;
+            pruned.challenges.fetch_add(challenges as u64, Ordering::Relaxed);
+            crate::metrics::pruned("challenge", challenges);
+            let sessions = data::Session::prune_old(&c)?;
+            pruned.sessions.fetch_add(sessions as u64, Ordering::Relaxed);
+            crate::metrics::pruned("session", sessions);
+            pruned.runs.fetch_add(1, Ordering::Relaxed);
+            tracing::debug!(challenges, sessions, "pruned expired records");
+            Ok(())
+        }).await;
+        match result {
+            Ok(Ok(())) => (),
+            Ok(Err(error)) => tracing::error!(%error, "pruning failed"),
+            Err(error) => tracing::error!(%error, "pruning failed")
+        }
+    }
+}
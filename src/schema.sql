@@ -1,9 +1,13 @@
+/* Foreign keys must reference a declared key, so referenced tables alias the rowid */
 CREATE TABLE `user` (
+    `rowid`         INTEGER PRIMARY KEY,
     `name`          TEXT,
     `contact`       TEXT,
     `image`         BLOB,
     `privilege`     INTEGER,
-    `pubkey`        TEXT
+    `pubkey`        TEXT,
+    /* The signature algorithm name, where NULL is inferred from the kind of key */
+    `algorithm`     TEXT
 );
 CREATE UNIQUE INDEX `idx_user_name`
     ON `user`(`name`);
@@ -12,19 +16,38 @@ CREATE TABLE `challenge` (
     `user`          INTEGER,
     `challenge`     TEXT,
     `expires`       DATETIME,
+    /* The request the challenge may be used for, where NULL allows any */
+    `method`        TEXT,
+    `path`          TEXT,
+    `purpose`       TEXT,
     FOREIGN KEY (`user`)            REFERENCES `user`(`rowid`)
 );
 CREATE UNIQUE INDEX `idx_challenge`
     ON `challenge`(`challenge`);
 
+CREATE TABLE `session` (
+    `user`          INTEGER,
+    /* SHA-256 hash of the bearer token */
+    `token`         TEXT,
+    `created`       DATETIME,
+    `expires`       DATETIME,
+    FOREIGN KEY (`user`)            REFERENCES `user`(`rowid`)
+);
+CREATE UNIQUE INDEX `idx_session`
+    ON `session`(`token`);
+
 CREATE TABLE `tag` (
+    `rowid`         INTEGER PRIMARY KEY,
     `tag`           TEXT
 );
 CREATE UNIQUE INDEX `idx_tag`
     ON `tag`(`tag`);
 
 CREATE TABLE `word` (
-    `word`          TEXT
+    `rowid`         INTEGER PRIMARY KEY,
+    `word`          TEXT,
+    `revision`      INTEGER DEFAULT 1,
+    `modified`      DATETIME
 );
 CREATE UNIQUE INDEX `idx_word`
     ON `word`(`word`);
@@ -34,20 +57,30 @@ CREATE TABLE `word_tag` (
     FOREIGN KEY (`word`)            REFERENCES `word`(`rowid`),
     FOREIGN KEY (`tag`)             REFERENCES `tag`(`rowid`)
 );
+CREATE INDEX `idx_word_tag`
+    ON `word_tag`(`word`);
 CREATE TABLE `word_reading` (
+    `rowid`         INTEGER PRIMARY KEY,
     `word`          INTEGER,
     `full`          TEXT,
     `accent`        TEXT,
     FOREIGN KEY (`word`)            REFERENCES `word`(`rowid`)
 );
+CREATE INDEX `idx_word_reading`
+    ON `word_reading`(`word`);
 CREATE TABLE `definition` (
     `word_reading`  INTEGER,
     `definition`    TEXT,
     FOREIGN KEY (`word_reading`)    REFERENCES `word_reading`(`rowid`)
 );
+CREATE INDEX `idx_definition`
+    ON `definition`(`word_reading`);
 CREATE TABLE `kanji` (
+    `rowid`         INTEGER PRIMARY KEY,
     `kanji`         CHAR(1),
-    `memonic`       TEXT
+    `memonic`       TEXT,
+    `revision`      INTEGER DEFAULT 1,
+    `modified`      DATETIME
 );
 CREATE UNIQUE INDEX `idx_kanji`
     ON `kanji`(`kanji`);
@@ -58,6 +91,7 @@ CREATE TABLE `kanji_tag` (
     FOREIGN KEY (`tag`)             REFERENCES `tag`(`rowid`)
 );
 CREATE TABLE `kanji_reading` (
+    `rowid`         INTEGER PRIMARY KEY,
     `kanji`         INTEGER,
     `memonic`       TEXT,
     FOREIGN KEY (`kanji`)           REFERENCES `kanji`(`rowid`)
@@ -80,4 +114,4 @@ CREATE TABLE `resource` (
     `name`          TEXT, 
     `kind`          TEXT,
     `uri`           TEXT
-);
\ No newline at end of file
+);
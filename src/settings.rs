@@ -0,0 +1,264 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    path::Path
+};
+
+use actix_web::http::{header::{HeaderName, HeaderValue}, Method, Uri};
+
+use crate::{data, Config};
+
+/// A configuration value that can be read from, and printed as, an environment variable
+trait EnvValue: Sized {
+    fn parse(value: &str) -> Result<Self, String>;
+    fn show(&self) -> String;
+}
+macro_rules! env_value {
+    ($($t:ty),*) => {$(
+        impl EnvValue for $t {
+            fn parse(value: &str) -> Result<Self, String> {
+                value.trim().parse().map_err(|e| format!("{}", e))
+            }
+            fn show(&self) -> String {
+                self.to_string()
+            }
+        }
+    )*};
+}
+env_value!(bool, u32, u64, usize, String, SocketAddr);
+/// An empty value unsets the field
+impl<T: EnvValue> EnvValue for Option<T> {
+    fn parse(value: &str) -> Result<Self, String> {
+        if value.trim().is_empty() {
+            Ok(None)
+        } else {
+            T::parse(value).map(Some)
+        }
+    }
+    fn show(&self) -> String {
+        self.as_ref().map(T::show).unwrap_or_default()
+    }
+}
+/// Items are separated by commas
+impl EnvValue for Vec<String> {
+    fn parse(value: &str) -> Result<Self, String> {
+        Ok(value.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(String::from)
+            .collect())
+    }
+    fn show(&self) -> String {
+        self.join(",")
+    }
+}
+
+/// The environment variable overriding a field
+fn variable(field: &str) -> String {
+    format!("OTANGO_{}", field.to_uppercase())
+}
+
+macro_rules! fields {
+    ($($field:ident),* $(,)?) => {
+        impl Config {
+            /// Override fields with any `OTANGO_<FIELD>` environment variables, returning those that could not be parsed
+            pub(crate) fn apply_env(&mut self) -> Vec<String> {
+                let mut problems = Vec::new();
+                $(
+                    if let Ok(value) = std::env::var(variable(stringify!($field))) {
+                        match EnvValue::parse(&value) {
+                            Ok(value) => self.$field = value,
+                            Err(e) => problems.push(format!("{} is invalid: {}", variable(stringify!($field)), e))
+                        }
+                    }
+                )*
+                problems
+            }
+            /// Print the effective configuration as the environment variables that would produce it
+            pub(crate) fn print(&self) {
+                $(
+                    println!("{}={}", variable(stringify!($field)), self.$field.show());
+                )*
+            }
+        }
+    };
+}
+fields!(
+    address,
+    tls,
+    redirect_address,
+    trusted_proxies,
+    key,
+    cert,
+    cert_expiry_warning,
+    cert_reload_interval,
+    database,
+    database_wal,
+    database_busy_timeout,
+    database_foreign_keys,
+    database_synchronous,
+    database_pool_size,
+    database_pool_timeout,
+    root_redirection,
+    sessions,
+    session_lifetime,
+    allowed_algorithms,
+    min_rsa_bits,
+    register_rate_limit,
+    register_rate_burst,
+    challenge_rate_limit,
+    challenge_rate_burst,
+    challenge_user_rate_limit,
+    challenge_user_rate_burst,
+    max_outstanding_challenges,
+    prune_interval,
+    log_level,
+    log_format,
+    slow_query_threshold,
+    metrics_address,
+    allowed_origins,
+    cors_methods,
+    cors_headers,
+    cors_expose_headers,
+    cors_credentials,
+    cors_max_age,
+    compression,
+    compression_threshold,
+    cache_capacity,
+    cache_ttl,
+    cache_control
+);
+
+impl Config {
+    /// Check the configuration, returning every problem found
+    pub(crate) fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.tls {
+            for (field, path) in [("key", &self.key), ("cert", &self.cert)] {
+                if let Err(e) = std::fs::File::open(path) {
+                    problems.push(format!("{} {:?} cannot be read: {}", field, path, e));
+                }
+            }
+            if Path::new(&self.cert).is_file() {
+                match crate::tls::expiry(&self.cert) {
+                    Ok(expiry) if expiry.days < 0 => problems.push(format!("cert {:?} expired {}", self.cert, expiry.not_after)),
+                    Ok(_) => (),
+                    Err(e) => problems.push(format!("cert {:?} is not a PEM encoded certificate: {}", self.cert, e))
+                }
+            }
+        }
+        if self.redirect_address.is_some() && !self.tls {
+            problems.push("redirect_address must not be set without tls, as there would be no HTTPS to redirect to".to_string());
+        }
+        for (field, address) in [("redirect_address", self.redirect_address), ("metrics_address", self.metrics_address)] {
+            if address == Some(self.address) {
+                problems.push(format!("{} must differ from address {}", field, self.address));
+            }
+        }
+        for proxy in &self.trusted_proxies {
+            if proxy.parse::<IpAddr>().is_err() {
+                problems.push(format!("trusted_proxies contains {:?}, which is not an IP address", proxy));
+            }
+        }
+
+        let database = Path::new(&self.database);
+        let directory = match database.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new(".")
+        };
+        if !directory.is_dir() {
+            problems.push(format!("database {:?} is in a directory that does not exist", self.database));
+        } else if !writable(directory) {
+            problems.push(format!("database {:?} is in a directory that cannot be written to, where SQLite creates its journal", self.database));
+        }
+        if std::fs::metadata(database).is_ok_and(|metadata| metadata.permissions().readonly()) {
+            problems.push(format!("database {:?} is read-only", self.database));
+        }
+        if !["OFF", "NORMAL", "FULL", "EXTRA"].contains(&self.database_synchronous.to_uppercase().as_str()) {
+            problems.push(format!("database_synchronous is {:?}, expected one of OFF, NORMAL, FULL or EXTRA", self.database_synchronous));
+        }
+        if self.database_pool_size == 0 {
+            problems.push("database_pool_size must be at least 1".to_string());
+        }
+
+        if let Some(location) = &self.root_redirection {
+            if location.parse::<Uri>().is_err() {
+                problems.push(format!("root_redirection {:?} is not a URI", location));
+            }
+        }
+        for algorithm in &self.allowed_algorithms {
+            if algorithm.parse::<data::Algorithm>().is_err() {
+                problems.push(format!("allowed_algorithms contains {:?}, expected any of RS256, PS256, ES256 or EdDSA", algorithm));
+            }
+        }
+        if self.allowed_algorithms.is_empty() {
+            problems.push("allowed_algorithms is empty, so no user could register".to_string());
+        }
+
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&self.log_level) {
+            problems.push(format!("log_level {:?} is not a valid filter: {}", self.log_level, e));
+        }
+        if !["text", "json"].contains(&self.log_format.as_str()) {
+            problems.push(format!("log_format is {:?}, expected text or json", self.log_format));
+        }
+
+        for origin in &self.allowed_origins {
+            if let Err(e) = check_origin(origin) {
+                problems.push(format!("allowed_origins contains {:?}, {}", origin, e));
+            }
+        }
+        if self.cors_credentials && self.allowed_origins.iter().any(|origin| origin == "*") {
+            problems.push("cors_credentials must not be set when allowed_origins contains *, as any site could make requests as the user".to_string());
+        }
+        for method in &self.cors_methods {
+            if Method::from_bytes(method.as_bytes()).is_err() {
+                problems.push(format!("cors_methods contains {:?}, which is not an HTTP method", method));
+            }
+        }
+        for (field, headers) in [("cors_headers", &self.cors_headers), ("cors_expose_headers", &self.cors_expose_headers)] {
+            for name in headers {
+                if HeaderName::from_bytes(name.as_bytes()).is_err() {
+                    problems.push(format!("{} contains {:?}, which is not a header name", field, name));
+                }
+            }
+        }
+        if HeaderValue::from_str(&self.cache_control).is_err() {
+            problems.push(format!("cache_control {:?} is not a valid header value", self.cache_control));
+        }
+        problems
+    }
+}
+
+/// Whether files can be created in `directory`, found by creating and removing one
+fn writable(directory: &Path) -> bool {
+    let probe = directory.join(format!(".otango-{}.probe", std::process::id()));
+    match std::fs::OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => std::fs::remove_file(&probe).is_ok(),
+        Err(e) => e.kind() == std::io::ErrorKind::AlreadyExists
+    }
+}
+
+/// Origins are a scheme and host, with an optional port
+fn check_origin(origin: &str) -> Result<(), &'static str> {
+    if origin == "*" {
+        return Ok(())
+    }
+    let uri = if origin.contains('*') {
+        crate::web::cors::Wildcard::new(origin)
+            .ok_or("where a wildcard may only replace the first label of the host, as in https://*.example.com")?;
+        origin.replacen('*', "subdomain", 1).parse::<Uri>()
+    } else {
+        origin.parse::<Uri>()
+    }
+        .map_err(|_| "which is not a URI")?;
+    if !matches!(uri.scheme_str(), Some("http" | "https")) {
+        return Err("which must begin with http:// or https://")
+    }
+    if uri.host().is_none() {
+        return Err("which has no host")
+    }
+    if uri.path_and_query().is_some_and(|path| path.as_str() != "/") || origin.ends_with('/') {
+        return Err("which must not have a path or a trailing slash")
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use std::{
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, SystemTime}
+};
+
+use openssl::{
+    asn1::Asn1Time,
+    ssl::{ClientHelloResponse, SslAcceptor, SslAcceptorBuilder, SslContext, SslFiletype, SslMethod},
+    x509::X509
+};
+
+use crate::prelude::*;
+
+/// When a certificate stops being valid
+pub struct Expiry {
+    /// The expiry time as written in the certificate
+    pub not_after: String,
+    /// Whole days until expiry, negative once expired
+    pub days: i32
+}
+
+/// Read the expiry of the leaf certificate in a PEM encoded chain
+pub fn expiry(cert: &str) -> Result<Expiry> {
+    let cert = X509::from_pem(&std::fs::read(cert)?)?;
+    let days = Asn1Time::days_from_now(0)?
+        .diff(cert.not_after())?
+        .days;
+    Ok(Expiry {
+        not_after: cert.not_after().to_string(),
+        days
+    })
+}
+
+/// A TLS context loaded from a key and certificate chain, which can be reloaded while serving.
+///
+/// Reloading only affects new connections, those already established keep their keys.
+pub struct Reloadable {
+    key: String,
+    cert: String,
+    context: RwLock<SslContext>,
+    /// When the files were last modified as of the last load
+    modified: Mutex<Option<SystemTime>>
+}
+impl Reloadable {
+    pub fn load(key: &str, cert: &str) -> Result<Arc<Self>> {
+        let tls = Self {
+            key: key.to_string(),
+            cert: cert.to_string(),
+            context: RwLock::new(Self::builder(key, cert)?.build().into_context()),
+            modified: Mutex::new(Self::modified(key, cert))
+        };
+        tls.log_expiry();
+        Ok(Arc::new(tls))
+    }
+    /// An acceptor which switches each new connection to the current context.
+    ///
+    /// The switch happens as the client hello is received, which unlike the SNI callback happens for
+    /// every connection, including clients that do not name the server.
+    pub fn acceptor(self: &Arc<Self>) -> Result<SslAcceptorBuilder> {
+        let mut builder = Self::builder(&self.key, &self.cert)?;
+        let tls = self.clone();
+        builder.set_client_hello_callback(move |ssl, _| {
+            let context = tls.context.read().unwrap();
+            ssl.set_ssl_context(&context)?;
+            Ok(ClientHelloResponse::SUCCESS)
+        });
+        Ok(builder)
+    }
+    /// Load the key and certificate again, keeping the current context if they are invalid
+    pub fn reload(&self) -> Result<()> {
+        let modified = Self::modified(&self.key, &self.cert);
+        let context = Self::builder(&self.key, &self.cert)?.build().into_context();
+        *self.context.write().unwrap() = context;
+        *self.modified.lock().unwrap() = modified;
+        self.log_expiry();
+        Ok(())
+    }
+    /// Reload if either file was modified since the last load
+    pub fn reload_if_changed(&self) -> Result<bool> {
+        let modified = Self::modified(&self.key, &self.cert);
+        if modified.is_none() || modified == *self.modified.lock().unwrap() {
+            return Ok(false)
+        }
+        self.reload()?;
+        Ok(true)
+    }
+    fn builder(key: &str, cert: &str) -> Result<SslAcceptorBuilder> {
+        let mut builder = SslAcceptor::mozilla_modern_v5(SslMethod::tls())?;
+        builder.set_private_key_file(key, SslFiletype::PEM)?;
+        builder.set_certificate_chain_file(cert)?;
+        builder.check_private_key()?;
+        Ok(builder)
+    }
+    /// The later modification time of the two files
+    fn modified(key: &str, cert: &str) -> Option<SystemTime> {
+        let modified = |path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        modified(key).max(modified(cert))
+    }
+    fn log_expiry(&self) {
+        match expiry(&self.cert) {
+            Ok(expiry) => tracing::info!(cert = %self.cert, expires = %expiry.not_after, days = expiry.days, "loaded certificate"),
+            Err(error) => tracing::warn!(cert = %self.cert, %error, "loaded certificate of unknown expiry")
+        }
+    }
+}
+
+/// Reload the TLS context whenever the process receives `SIGHUP`
+#[cfg(unix)]
+pub async fn reload_on_hangup(tls: Arc<Reloadable>) {
+    use actix_web::rt::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(error) => return tracing::error!(%error, "cannot listen for SIGHUP")
+    };
+    while hangup.recv().await.is_some() {
+        if let Err(error) = tls.reload() {
+            tracing::error!(%error, "reloading certificate failed");
+        }
+    }
+}
+
+/// Reload the TLS context whenever the key or certificate file changes, checking every `interval`
+pub async fn watch(tls: Arc<Reloadable>, interval: Duration) {
+    let mut interval = actix_web::rt::time::interval(interval);
+    loop {
+        interval.tick().await;
+        if let Err(error) = tls.reload_if_changed() {
+            tracing::error!(%error, "reloading certificate failed");
+        }
+    }
+}
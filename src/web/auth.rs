@@ -1,28 +1,238 @@
 use super::prelude::*;
 
-#[post("/auth/register")]
-async fn register(db: Data<Pool>, signed: Json<data::Signed<data::Certificate>>) -> Result<impl Responder> {
-    let cert = signed.into_inner().verify()?;
-
-    let user = data::User {
-        name: cert.name,
-        contact: cert.contact,
-        image: None,
-        privilege: data::Privilege::None,
-        pubkey: cert.pubkey
+use actix_web::http::header;
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+
+/// The session token given in the `Authorization` header, if any
+pub fn bearer(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(|token| token.trim().to_string())
+}
+
+/// Describe a request for checking against the scope of the challenge it was signed with
+pub fn scope(req: &HttpRequest, purpose: &str) -> data::Scope {
+    data::Scope {
+        method: Some(req.method().to_string()),
+        path: Some(req.path().to_string()),
+        purpose: Some(purpose.to_string())
+    }
+}
+
+/// Authenticate a request body, returning the user that made it along with the data.
+/// 
+/// A request with a session token is authenticated by the session, and its body is just the data.
+/// Otherwise the body must be a `Signed<By<T>>` using a challenge issued for the `request`.
+pub fn authorise<T: DeserializeOwned>(c: &data::Connection, token: Option<&str>, body: &[u8], requires_privilege: data::Privilege, request: &data::Scope) -> Result<(String, T)> {
+    let (user, data) = match token {
+        Some(token) => {
+            if !sessions_enabled() {
+                return Err(Error::Session)
+            }
+            let (user, privilege) = data::Session::authenticate(c, token)?
+                .ok_or(Error::Session)?;
+            if privilege >= requires_privilege {
+                (user, serde_json::from_slice(body).map_err(Error::Body)?)
+            } else {
+                return Err(Error::Privilege)
+            }
+        },
+        None => serde_json::from_slice::<Signed<By<T>>>(body)
+            .map_err(Error::Body)?
+            .identify(c, requires_privilege, request)?
     };
+    record_user(&user);
+    Ok((user, data))
+}
 
-    let c = web::block(move || db.get())
-        .await??;
-    web::block(move || user.insert(&c))
-        .await??;
+/// Attribute the current request to an authenticated user in the logs
+fn record_user(user: &str) {
+    tracing::Span::current().record("user", user);
+}
+
+fn sessions_enabled() -> bool {
+    crate::CONFIG.get().map(|c| c.sessions).unwrap_or(false)
+}
+
+/// Register a user with a self-signed certificate
+#[utoipa::path(request_body = data::Signed<data::Certificate>, responses(
+    (status = 201, description = "The user was registered")
+))]
+#[post("/auth/register")]
+async fn register(db: Data<Pool>, signed: Json<data::Signed<data::Certificate>>) -> Result<impl Responder> {
+    let policy = crate::CONFIG.get()
+        .map(|config| config.key_policy())
+        .unwrap_or_default();
+    with_db(&db, move |c| {
+        let cert = signed.into_inner().verify(&policy)?;
+        data::User {
+            name: cert.name,
+            contact: cert.contact,
+            image: None,
+            privilege: data::Privilege::None,
+            pubkey: cert.pubkey,
+            algorithm: cert.algorithm
+        }.insert(c)
+    }).await?;
 
     Ok(HttpResponse::Created())
 }
 
+/// Get a challenge to sign a request with
+#[utoipa::path(request_body = Signed<data::ChallengeRequest>, responses(
+    (status = 200, description = "The challenge, as a JSON string", body = String, content_type = "text/plain"),
+    (status = 429, description = "Too many challenges were requested")
+))]
 #[post("/auth/challenge")]
-async fn challenge(db: Data<Pool>, signed: Json<Signed<String>>) -> Result<impl Responder> {
-    let c = web::block(move || db.get())
-        .await??;
-    return Ok(serde_json::to_string(&data::Challenge::generate(&c, &signed.into_inner().verify_user(&c)?)?)?);
-}
\ No newline at end of file
+async fn challenge(db: Data<Pool>, limiter: Data<super::limit::Limiter<String>>, signed: Json<Signed<data::ChallengeRequest>>) -> Result<impl Responder> {
+    let max_outstanding = crate::CONFIG.get()
+        .map(|config| config.max_outstanding_challenges)
+        .unwrap_or(0);
+    let challenge = with_db(&db, move |c| {
+        let request = signed.into_inner().verify_user(c)?;
+        record_user(request.user());
+        limiter.check(request.user().to_string())
+            .map_err(Error::RateLimited)?;
+        if max_outstanding > 0 {
+            if let (outstanding, Some(first_expiry)) = data::Challenge::outstanding(c, request.user())? {
+                if outstanding >= max_outstanding {
+                    let retry = (first_expiry - Utc::now()).to_std().unwrap_or_default();
+                    return Err(Error::RateLimited(retry))
+                }
+            }
+        }
+        data::Challenge::generate(c, request.user(), &request.scope())
+    }).await?;
+    Ok(serde_json::to_string(&challenge)?)
+}
+
+/// Exchange a signed request for a session token
+#[utoipa::path(request_body = Signed<By<utoipa::TupleUnit>>, responses(
+    (status = 201, description = "The session token", content(
+        (data::Token = "application/json"), (data::Token = "application/msgpack"), (data::Token = "application/cbor")
+    )),
+    (status = 404, description = "Sessions are disabled")
+))]
+#[post("/auth/login")]
+async fn login(req: HttpRequest, db: Data<Pool>, signed: Json<Signed<By<()>>>) -> Result<impl Responder> {
+    let lifetime = match crate::CONFIG.get() {
+        Some(config) if config.sessions => chrono::Duration::minutes(config.session_lifetime as i64),
+        _ => return Ok(HttpResponse::NotFound().finish())
+    };
+    let request = scope(&req, "login");
+    let token = with_db(&db, move |c| {
+        let (user, ()) = signed.into_inner().identify(c, data::Privilege::None, &request)?;
+        record_user(&user);
+        data::Session::create(c, &user, lifetime)
+    }).await?;
+
+    super::format::respond(&req, HttpResponse::Created(), &token)
+}
+
+/// Revoke the session token
+#[utoipa::path(security(("session" = [])), responses(
+    (status = 204, description = "The session was revoked"),
+    (status = 401, description = "The session was invalid")
+))]
+#[post("/auth/logout")]
+async fn logout(req: HttpRequest, db: Data<Pool>) -> Result<impl Responder> {
+    let token = bearer(&req)
+        .ok_or(Error::Session)?;
+    if with_db(&db, move |c| data::Session::revoke(c, &token)).await? {
+        Ok(HttpResponse::NoContent())
+    } else {
+        Err(Error::Session)
+    }
+}
+
+/// List the sessions of the user
+#[utoipa::path(security(("session" = [])), responses(
+    (status = 200, description = "The sessions", content(
+        (Vec<data::Session> = "application/json"), (Vec<data::Session> = "application/msgpack"), (Vec<data::Session> = "application/cbor")
+    )),
+    (status = 401, description = "The session was invalid")
+))]
+#[get("/auth/sessions")]
+async fn sessions(req: HttpRequest, db: Data<Pool>) -> Result<impl Responder> {
+    let token = bearer(&req)
+        .ok_or(Error::Session)?;
+    let sessions = with_db(&db, move |c| {
+        let (user, _) = data::Session::authenticate(c, &token)?
+            .ok_or(Error::Session)?;
+        record_user(&user);
+        data::Session::for_user(c, &user)
+    }).await?;
+
+    super::format::respond(&req, HttpResponse::Ok(), &sessions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use actix_web::{http::StatusCode, test, App};
+    use openssl::{ec::{EcGroup, EcKey}, nid::Nid, pkey::PKey};
+
+    /// Run with `--nocapture` to see the throughput
+    #[actix_web::test]
+    async fn concurrent_challenges() {
+        const REQUESTS: usize = 200;
+        let directory = tempfile::tempdir().unwrap();
+        let pool = crate::tests::config(&directory).pool(8).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap()).unwrap()).unwrap();
+        {
+            let c = pool.get().unwrap();
+            data::migrate(&c).unwrap();
+            data::User {
+                name: "user".into(),
+                contact: None,
+                image: None,
+                privilege: data::Privilege::None,
+                pubkey: String::from_utf8(key.public_key_to_pem().unwrap()).unwrap(),
+                algorithm: data::Algorithm::EcdsaP256Sha256
+            }.insert(&c).unwrap();
+        }
+        let app = test::init_service(App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(super::super::limit::Limiter::<String>::new(0, 0)))
+            .service(challenge)
+        ).await;
+
+        let requests: Vec<_> = (0..REQUESTS).map(|_| {
+            let signed = Signed::new(data::ChallengeRequest::User("user".into()), |bytes| data::Algorithm::EcdsaP256Sha256.sign(&key, bytes))
+                .unwrap();
+            test::TestRequest::post()
+                .uri("/auth/challenge")
+                .set_json(&signed)
+                .to_request()
+        }).collect();
+        let start = Instant::now();
+        let responses = futures_util::future::join_all(requests.into_iter().map(|request| test::call_service(&app, request)))
+            .await;
+        let elapsed = start.elapsed();
+        println!("{} concurrent challenges in {:?}, {:.0} per second", REQUESTS, elapsed, REQUESTS as f64 / elapsed.as_secs_f64());
+
+        for response in responses {
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let issued: i64 = pool.get().unwrap()
+            .query_row("SELECT COUNT(*) FROM `challenge`", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(issued, REQUESTS as i64);
+    }
+    #[actix_web::test]
+    async fn malformed_body_is_a_bad_request() {
+        use actix_web::ResponseError;
+        let pool = data::memory();
+        let c = pool.get().unwrap();
+        for body in [&b"{"[..], b"[]", br#"{"data":{},"signature":"not base64"}"#] {
+            let error = authorise::<data::Word>(&c, None, body, data::Privilege::Admin, &data::Scope::default())
+                .expect_err("a malformed body was authorised");
+            assert_eq!(error.status_code(), StatusCode::BAD_REQUEST, "{}", error);
+        }
+    }
+}
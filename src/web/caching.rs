@@ -0,0 +1,56 @@
+use super::prelude::*;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::{
+    http::header::{self, EntityTag, HttpDate, IfModifiedSince, IfNoneMatch, LastModified},
+    HttpMessage
+};
+
+use super::format::Format;
+
+/// Respond with a dictionary entry and the headers needed to cache it,
+/// or with `304 Not Modified` if the client already has the current revision.
+///
+/// Each format is a different representation, so it is part of the entity tag.
+pub fn entry<T: Serialize>(req: &HttpRequest, revision: &data::Revision, entry: &T) -> Result<HttpResponse> {
+    let format = Format::negotiate(req);
+    let etag = match format {
+        Format::Json => EntityTag::new_strong(revision.tag()),
+        format => EntityTag::new_strong(format!("{}-{}", revision.tag(), format.name()))
+    };
+    // If-None-Match takes precedence over If-Modified-Since when both are present
+    let not_modified = match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        None => match (req.get_header::<IfModifiedSince>(), &revision.modified) {
+            (Some(IfModifiedSince(since)), Some(modified)) => SystemTime::from(since)
+                .duration_since(UNIX_EPOCH)
+                .map(|since| modified.timestamp() <= since.as_secs() as i64)
+                .unwrap_or(false),
+            _ => false
+        }
+    };
+
+    let mut response = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    response.insert_header(header::ETag(etag));
+    if let Some(modified) = revision.modified {
+        response.insert_header(LastModified(HttpDate::from(SystemTime::from(modified))));
+    }
+    if let Some(cache_control) = crate::CONFIG.get().map(|c| c.cache_control.as_str()).filter(|c| !c.is_empty()) {
+        response.insert_header((header::CACHE_CONTROL, cache_control));
+    }
+
+    if not_modified {
+        Ok(response
+            .insert_header((header::VARY, "Accept"))
+            .finish()
+        )
+    } else {
+        format.body(response, entry)
+    }
+}
@@ -0,0 +1,69 @@
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin
+};
+
+use actix_web::{
+    body::{BodySize, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{self, HeaderValue}
+};
+
+/// Middleware marking responses smaller than a threshold as `identity` encoded,
+/// so that the `Compress` middleware wrapping it leaves them alone.
+///
+/// Compressing small bodies costs more time than it saves in transfer, and can even make them larger.
+#[derive(Clone)]
+pub struct Threshold(pub usize);
+impl<S, B> Transform<S, ServiceRequest> for Threshold
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = ThresholdMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ThresholdMiddleware {
+            service,
+            threshold: self.0 as u64
+        }))
+    }
+}
+
+pub struct ThresholdMiddleware<S> {
+    service: S,
+    threshold: u64
+}
+impl<S, B> Service<ServiceRequest> for ThresholdMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let threshold = self.threshold;
+        let response = self.service.call(req);
+        Box::pin(async move {
+            let mut response = response.await?;
+            let small = match response.response().body().size() {
+                BodySize::Sized(size) => size < threshold,
+                BodySize::None | BodySize::Stream => false
+            };
+            if small && !response.headers().contains_key(header::CONTENT_ENCODING) {
+                response.headers_mut().insert(header::CONTENT_ENCODING, HeaderValue::from_static("identity"));
+            }
+            Ok(response)
+        })
+    }
+}
@@ -0,0 +1,65 @@
+use actix_cors::Cors;
+
+/// The CORS middleware for the configuration.
+///
+/// Cross-origin requests are denied unless `allowed_origins` is set. Origins may be exact, such as
+/// `https://example.com`, match any subdomain, such as `https://*.example.com`, or be `*` for any origin.
+pub fn cors() -> Cors {
+    let config = match crate::CONFIG.get() {
+        Some(config) => config,
+        None => return Cors::default()
+    };
+    let mut cors = Cors::default()
+        .allowed_methods(config.cors_methods.iter().map(String::as_str))
+        .allowed_headers(config.cors_headers.iter().map(String::as_str))
+        .expose_headers(config.cors_expose_headers.iter().map(String::as_str))
+        .max_age((config.cors_max_age > 0).then_some(config.cors_max_age));
+    if config.cors_credentials {
+        cors = cors.supports_credentials();
+    }
+
+    let (wildcards, exact): (Vec<_>, Vec<_>) = config.allowed_origins.iter()
+        .partition(|origin| origin.contains('*'));
+    if wildcards.iter().any(|origin| origin.as_str() == "*") {
+        return cors.allow_any_origin()
+    }
+    for origin in exact {
+        cors = cors.allowed_origin(origin);
+    }
+    if !wildcards.is_empty() {
+        let wildcards = wildcards.into_iter()
+            .filter_map(|origin| Wildcard::new(origin))
+            .collect::<Vec<_>>();
+        cors = cors.allowed_origin_fn(move |origin, _| origin.to_str()
+            .is_ok_and(|origin| wildcards.iter().any(|wildcard| wildcard.matches(origin)))
+        );
+    }
+    cors
+}
+
+/// An origin matching any subdomain, such as `https://*.example.com`
+pub struct Wildcard {
+    /// The scheme up to the wildcard, such as `https://`
+    scheme: String,
+    /// The rest of the origin after the wildcard, such as `.example.com`
+    domain: String
+}
+impl Wildcard {
+    pub fn new(origin: &str) -> Option<Self> {
+        let (scheme, domain) = origin.split_once("://*.")?;
+        if domain.is_empty() || domain.contains(['*', '/']) {
+            return None
+        }
+        Some(Self {
+            scheme: format!("{}://", scheme),
+            domain: format!(".{}", domain)
+        })
+    }
+    pub fn matches(&self, origin: &str) -> bool {
+        origin.strip_prefix(&self.scheme)
+            .and_then(|origin| origin.strip_suffix(&self.domain))
+            .is_some_and(|subdomain| !subdomain.is_empty()
+                && subdomain.split('.').all(|label| !label.is_empty()
+                    && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')))
+    }
+}
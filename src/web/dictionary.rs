@@ -1,45 +1,232 @@
 use super::prelude::*;
 
+/// The largest number of entries that can be fetched in one batch
+const MAX_BATCH: usize = 256;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct WordListing {
+    /// Sort by reading or by most recently added
+    #[serde(default)]
+    #[param(inline)]
+    order: data::WordOrder,
+    /// Only list words with this tag
+    tag: Option<String>,
+    /// The `next` cursor of the previous page
+    after: Option<String>,
+    /// The most words to list, 50 by default and reduced to the largest page allowed
+    limit: Option<u32>
+}
+/// List words a page at a time
+#[utoipa::path(params(WordListing), responses(
+    (status = 200, description = "A page of words", content(
+        (data::WordPage = "application/json"), (data::WordPage = "application/msgpack"), (data::WordPage = "application/cbor")
+    ))
+))]
+#[get("/word")]
+async fn list_words(req: HttpRequest, db: Data<Pool>, query: Query<WordListing>) -> Result<impl Responder> {
+    let query = query.into_inner();
+    let limit = query.limit.unwrap_or(50).clamp(1, data::WordSummary::MAX_PAGE);
+    let page = with_db(&db, move |c| data::WordSummary::page(c, query.order, query.tag.as_deref(), query.after.as_deref(), limit))
+        .await?;
+
+    super::format::respond(&req, HttpResponse::Ok(), &page)
+}
+
+/// Authorise a word sent by an administrator, which must be the word named by the path
+fn authorise_word(c: &data::Connection, token: Option<&str>, body: &[u8], request: &data::Scope, path: String) -> Result<data::Word> {
+    let (_, word): (_, data::Word) = super::auth::authorise(c, token, body, data::Privilege::Admin, request)?;
+    if word.word != path {
+        return Err(Error::PathMismatch { path, body: word.word })
+    }
+    Ok(word)
+}
+/// Add a word, as an administrator
+#[utoipa::path(
+    params(("word" = String, Path, description = "The word")),
+    request_body = super::openapi::Authorised<data::Word>,
+    security((), ("session" = [])),
+    responses(
+        (status = 204, description = "The word was added"),
+        (status = 400, description = "The path is for a different word than the body"),
+        (status = 409, description = "The word already exists")
+    )
+)]
 #[post("/word/{word}")]
-async fn set_word(db: Data<Pool>, path: Path<String>, signed: Json<Signed<By<data::Word>>>) -> Result<impl Responder> {
-    let c = web::block(move || db.get())
-        .await??;
-    let word = signed.into_inner().privileged(&c)?;
-    web::block(move || word.insert(&c)).await??;
+async fn set_word(req: HttpRequest, db: Data<Pool>, cache: Data<cache::Dictionary>, path: Path<String>, body: web::Bytes) -> Result<impl Responder> {
+    let token = super::auth::bearer(&req);
+    let request = super::auth::scope(&req, "set_word");
+    let path = path.into_inner();
+    with_db(&db, move |c| authorise_word(c, token.as_deref(), &body, &request, path)?.insert(c))
+        .await?;
+    cache.invalidate();
     Ok(HttpResponse::NoContent())
 }
+/// Replace the readings and tags of a word, as an administrator
+#[utoipa::path(
+    params(("word" = String, Path, description = "The word")),
+    request_body = super::openapi::Authorised<data::Word>,
+    security((), ("session" = [])),
+    responses(
+        (status = 204, description = "The word was updated to its next revision"),
+        (status = 400, description = "The path is for a different word than the body"),
+        (status = 404, description = "There is no such word")
+    )
+)]
+#[put("/word/{word}")]
+async fn update_word(req: HttpRequest, db: Data<Pool>, cache: Data<cache::Dictionary>, path: Path<String>, body: web::Bytes) -> Result<impl Responder> {
+    let token = super::auth::bearer(&req);
+    let request = super::auth::scope(&req, "update_word");
+    let path = path.into_inner();
+    let updated = with_db(&db, move |c| authorise_word(c, token.as_deref(), &body, &request, path)?.update(c))
+        .await?;
+    cache.invalidate();
+    Ok(if updated {
+        HttpResponse::NoContent()
+    } else {
+        HttpResponse::NotFound()
+    })
+}
+/// Get a word
+#[utoipa::path(params(("word" = String, Path, description = "The word")), responses(
+    (status = 200, description = "The word", content(
+        (data::Word = "application/json"), (data::Word = "application/msgpack"), (data::Word = "application/cbor")
+    )),
+    (status = 304, description = "The cached revision is current"),
+    (status = 404, description = "There is no such word")
+))]
 #[get("/word/{word}")]
-async fn get_word(db: Data<Pool>, path: Path<String>) -> Result<impl Responder> {
+async fn get_word(req: HttpRequest, db: Data<Pool>, cache: Data<cache::Dictionary>, path: Path<String>) -> Result<impl Responder> {
     let word = path.into_inner();
-    let c = web::block(move || db.get())
-        .await??;
-    let word = web::block(move || data::Word::get(&c, &word))
-        .await??;
+    let generation = cache.words.generation();
+    let word = match cache.words.get(&word) {
+        Some(word) => Some(word),
+        None => {
+            let key = word.clone();
+            with_db(&db, move |c| data::Word::get(c, &word))
+                .await?
+                .map(|word| cache.words.insert(generation, key, word))
+        }
+    };
 
     if let Some(word) = word {
-        Ok(HttpResponse::Ok()
-            .content_type(ContentType::json())
-            .body(serde_json::to_string(&word)?)
-        )
+        super::caching::entry(&req, &word.revision, &*word)
     } else {
         Ok(HttpResponse::NotFound().finish())
     }
 }
 
+/// Get many words at once
+#[utoipa::path(request_body = Vec<String>, responses(
+    (status = 200, description = "The words that exist, by word", content(
+        (HashMap<String, data::Word> = "application/json"),
+        (HashMap<String, data::Word> = "application/msgpack"),
+        (HashMap<String, data::Word> = "application/cbor")
+    )),
+    (status = 400, description = "Too many words were requested")
+))]
+#[post("/word/batch")]
+async fn get_words(req: HttpRequest, db: Data<Pool>, words: Json<Vec<String>>) -> Result<impl Responder> {
+    let words = words.into_inner();
+    if words.len() > MAX_BATCH {
+        return Err(Error::TooMany(MAX_BATCH))
+    }
+    let words = with_db(&db, move |c| data::Word::get_many(c, &words))
+        .await?;
+
+    super::format::respond(&req, HttpResponse::Ok(), &words)
+}
+
+/// Get a kanji
+#[utoipa::path(params(("kanji" = char, Path, description = "The kanji")), responses(
+    (status = 200, description = "The kanji", content(
+        (data::Kanji = "application/json"), (data::Kanji = "application/msgpack"), (data::Kanji = "application/cbor")
+    )),
+    (status = 304, description = "The cached revision is current"),
+    (status = 404, description = "There is no such kanji")
+))]
 #[get("/kanji/{kanji}")]
-async fn get_kanji(db: Data<Pool>, path: Path<char>) -> Result<impl Responder> {
+async fn get_kanji(req: HttpRequest, db: Data<Pool>, cache: Data<cache::Dictionary>, path: Path<char>) -> Result<impl Responder> {
     let kanji = path.into_inner();
-    let c = web::block(move || db.get())
-        .await??;
-    let kanji = web::block(move || data::Kanji::get(&c, kanji))
-        .await??;
+    let generation = cache.kanji.generation();
+    let kanji = match cache.kanji.get(&kanji) {
+        Some(kanji) => Some(kanji),
+        None => {
+            with_db(&db, move |c| Ok(data::Kanji::get(c, kanji)?))
+                .await?
+                .map(|found| cache.kanji.insert(generation, kanji, found))
+        }
+    };
 
     if let Some(kanji) = kanji {
-        Ok(HttpResponse::Ok()
-            .content_type(ContentType::json())
-            .body(serde_json::to_string(&kanji)?)
-        )
+        super::caching::entry(&req, &kanji.revision, &*kanji)
     } else {
         Ok(HttpResponse::NotFound().finish())
     }
-}
\ No newline at end of file
+}
+
+/// Get many kanji at once
+#[utoipa::path(request_body = Vec<char>, responses(
+    (status = 200, description = "The kanji that exist, by kanji", content(
+        (HashMap<char, data::Kanji> = "application/json"),
+        (HashMap<char, data::Kanji> = "application/msgpack"),
+        (HashMap<char, data::Kanji> = "application/cbor")
+    )),
+    (status = 400, description = "Too many kanji were requested")
+))]
+#[post("/kanji/batch")]
+async fn get_kanji_batch(req: HttpRequest, db: Data<Pool>, kanji: Json<Vec<char>>) -> Result<impl Responder> {
+    let kanji = kanji.into_inner();
+    if kanji.len() > MAX_BATCH {
+        return Err(Error::TooMany(MAX_BATCH))
+    }
+    let kanji = with_db(&db, move |c| data::Kanji::get_many(c, &kanji))
+        .await?;
+
+    super::format::respond(&req, HttpResponse::Ok(), &kanji)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+    use openssl::{ec::{EcGroup, EcKey}, nid::Nid, pkey::PKey};
+
+    #[actix_web::test]
+    async fn set_word_must_match_the_path() {
+        let pool = data::memory();
+        let key = PKey::from_ec_key(EcKey::generate(&EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap()).unwrap()).unwrap();
+        let challenge = {
+            let c = pool.get().unwrap();
+            data::User {
+                name: "admin".into(),
+                contact: None,
+                image: None,
+                privilege: data::Privilege::Admin,
+                pubkey: String::from_utf8(key.public_key_to_pem().unwrap()).unwrap(),
+                algorithm: data::Algorithm::EcdsaP256Sha256
+            }.insert(&c).unwrap();
+            data::Challenge::generate(&c, "admin", &data::Scope::default()).unwrap()
+        };
+        let app = test::init_service(App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(cache::Dictionary::new(16, std::time::Duration::from_secs(60))))
+            .service(set_word)
+        ).await;
+
+        let word = data::Word {
+            word: "other".into(),
+            readings: Vec::new(),
+            tags: Vec::new(),
+            revision: Default::default()
+        };
+        let signed = Signed::new(By::new("admin".into(), challenge, word), |bytes| data::Algorithm::EcdsaP256Sha256.sign(&key, bytes))
+            .unwrap();
+        let request = test::TestRequest::post()
+            .uri("/word/word")
+            .set_json(&signed)
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+        assert!(data::Word::get(&pool.get().unwrap(), "other").unwrap().is_none());
+    }
+}
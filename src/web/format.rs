@@ -0,0 +1,75 @@
+use super::prelude::*;
+
+use actix_web::{
+    http::header::{self, Accept},
+    HttpMessage,
+    HttpResponseBuilder
+};
+
+/// A representation of response data, chosen by the `Accept` header of the request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    MessagePack,
+    Cbor
+}
+impl Format {
+    /// The most preferred format the client accepts, or JSON if it accepts none of them
+    pub fn negotiate(req: &HttpRequest) -> Self {
+        req.get_header::<Accept>()
+            .and_then(|accept| accept.ranked().into_iter()
+                .find_map(|mime| Self::from_essence(mime.essence_str())))
+            .unwrap_or(Self::Json)
+    }
+    fn from_essence(essence: &str) -> Option<Self> {
+        match essence {
+            "application/json" | "application/*" | "*/*" => Some(Self::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(Self::MessagePack),
+            "application/cbor" => Some(Self::Cbor),
+            _ => None
+        }
+    }
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::MessagePack => "application/msgpack",
+            Self::Cbor => "application/cbor"
+        }
+    }
+    /// A short name distinguishing representations, such as in entity tags
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::MessagePack => "msgpack",
+            Self::Cbor => "cbor"
+        }
+    }
+    pub fn encode<T: Serialize + ?Sized>(self, value: &T) -> Result<Vec<u8>> {
+        match self {
+            Self::Json => Ok(serde_json::to_vec(value)?),
+            // Structs are encoded as maps so that fields are named, as in JSON
+            Self::MessagePack => rmp_serde::to_vec_named(value)
+                .map_err(|e| Error::Other(Box::new(e))),
+            Self::Cbor => {
+                let mut buffer = Vec::new();
+                ciborium::ser::into_writer(value, &mut buffer)
+                    .map_err(|e| Error::Other(Box::new(e)))?;
+                Ok(buffer)
+            }
+        }
+    }
+    /// Finish a response with the value as its body
+    pub fn body<T: Serialize + ?Sized>(self, mut response: HttpResponseBuilder, value: &T) -> Result<HttpResponse> {
+        let body = self.encode(value)?;
+        Ok(response
+            .insert_header((header::VARY, "Accept"))
+            .content_type(self.content_type())
+            .body(body)
+        )
+    }
+}
+
+/// Respond with the value in the format the client prefers
+pub fn respond<T: Serialize + ?Sized>(req: &HttpRequest, response: HttpResponseBuilder, value: &T) -> Result<HttpResponse> {
+    Format::negotiate(req).body(response, value)
+}
@@ -0,0 +1,79 @@
+use super::prelude::*;
+
+use std::collections::BTreeMap;
+
+#[derive(Serialize, ToSchema)]
+struct Check {
+    ok: bool,
+    detail: String
+}
+impl Check {
+    fn new(result: Result<(bool, String)>) -> Self {
+        match result {
+            Ok((ok, detail)) => Self { ok, detail },
+            Err(e) => Self { ok: false, detail: e.to_string() }
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct Readiness {
+    ready: bool,
+    checks: BTreeMap<&'static str, Check>
+}
+
+/// The process is up and able to respond
+#[utoipa::path(responses(
+    (status = 200, description = "The server is running", body = serde_json::Value, example = json!({ "status": "ok" }))
+))]
+#[get("/health")]
+async fn health() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type(ContentType::json())
+        .body(r#"{"status":"ok"}"#)
+}
+
+/// The server is able to handle requests: the database is reachable with the expected schema,
+/// and the TLS certificate, if serving HTTPS, is not close to expiring
+#[utoipa::path(responses(
+    (status = 200, description = "Every check passed", body = Readiness),
+    (status = 503, description = "A check failed", body = Readiness)
+))]
+#[get("/ready")]
+async fn ready(db: Data<Pool>) -> Result<impl Responder> {
+    let mut checks = BTreeMap::new();
+
+    let schema = with_db(&db, |c| data::schema_version(c)).await;
+    checks.insert("database", Check::new(schema.as_ref()
+        .map(|_| (true, "reachable".to_string()))
+        .map_err(|e| Error::Other(e.to_string().into()))
+    ));
+    checks.insert("schema", Check::new(schema.map(|version| (
+        version == data::SCHEMA_VERSION,
+        format!("version {}, expected {}", version, data::SCHEMA_VERSION)
+    ))));
+
+    if let Some(config) = crate::CONFIG.get().filter(|config| config.tls) {
+        let warning = config.cert_expiry_warning as i32;
+        let certificate = web::block(move || crate::tls::expiry(&config.cert))
+            .await?;
+        checks.insert("certificate", Check::new(certificate.map(|expiry| (
+            expiry.days >= warning,
+            format!("expires {}, in {} days", expiry.not_after, expiry.days)
+        ))));
+    }
+
+    let readiness = Readiness {
+        ready: checks.values().all(|check| check.ok),
+        checks
+    };
+    let mut response = if readiness.ready {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::ServiceUnavailable()
+    };
+    Ok(response
+        .content_type(ContentType::json())
+        .body(serde_json::to_string(&readiness)?)
+    )
+}
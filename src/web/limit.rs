@@ -0,0 +1,247 @@
+use std::{
+    collections::HashMap,
+    future::{ready, Future, Ready},
+    hash::Hash,
+    net::IpAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant}
+};
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::Method
+};
+
+use crate::Error;
+
+/// Token buckets limiting how often something may happen for each key
+pub struct Limiter<K> {
+    /// Tokens added to each bucket per second
+    rate: f64,
+    /// The most tokens a bucket can hold
+    burst: f64,
+    /// The most buckets kept, bounding memory use however many keys are seen
+    capacity: usize,
+    buckets: Mutex<HashMap<K, Bucket>>
+}
+struct Bucket {
+    tokens: f64,
+    updated: Instant
+}
+impl<K: Hash + Eq> Limiter<K> {
+    /// Idle buckets are pruned once there are this many
+    const PRUNE_AT: usize = 4096;
+    /// The default capacity
+    const CAPACITY: usize = 65536;
+    /// Allow `per_minute` events per minute on average, in bursts of up to `burst`.
+    ///
+    /// A rate of 0 disables the limit.
+    pub fn new(per_minute: u32, burst: u32) -> Self {
+        Self {
+            rate: per_minute as f64 / 60.0,
+            burst: burst.max(1) as f64,
+            capacity: Self::CAPACITY,
+            buckets: Mutex::new(HashMap::new())
+        }
+    }
+    /// Keep at most `capacity` buckets. Once full, the least recently used are forgotten for new keys,
+    /// which at worst lets those keys burst again.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+    /// Take a token from the bucket for `key`, or get how long until one will be available
+    pub fn check(&self, key: K) -> std::result::Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+    fn check_at(&self, key: K, now: Instant) -> std::result::Result<(), Duration> {
+        if self.rate <= 0.0 {
+            return Ok(())
+        }
+        let mut buckets = self.buckets.lock().unwrap();
+        if !buckets.contains_key(&key) {
+            if buckets.len() >= Self::PRUNE_AT.min(self.capacity) {
+                self.retain_active(&mut buckets, now);
+            }
+            if buckets.len() >= self.capacity {
+                Self::evict_oldest(&mut buckets);
+            }
+        }
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now
+        });
+        bucket.tokens = self.refill(bucket, now);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+    /// Forget the buckets that have refilled, as they are no different to new ones
+    pub fn prune(&self) {
+        let mut buckets = self.buckets.lock().unwrap();
+        self.retain_active(&mut buckets, Instant::now());
+    }
+    fn retain_active(&self, buckets: &mut HashMap<K, Bucket>, now: Instant) {
+        buckets.retain(|_, bucket| self.refill(bucket, now) < self.burst);
+    }
+    /// Forget the least recently used quarter of the buckets
+    fn evict_oldest(buckets: &mut HashMap<K, Bucket>) {
+        let mut updated: Vec<Instant> = buckets.values().map(|bucket| bucket.updated).collect();
+        let index = updated.len() / 4;
+        let (_, &mut cutoff, _) = updated.select_nth_unstable(index);
+        buckets.retain(|_, bucket| bucket.updated > cutoff);
+    }
+    fn refill(&self, bucket: &Bucket, now: Instant) -> f64 {
+        (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * self.rate).min(self.burst)
+    }
+}
+
+/// Middleware rate limiting requests to chosen routes by the IP address of the client,
+/// as forwarded by any trusted proxy
+#[derive(Clone, Default)]
+pub struct RateLimit {
+    routes: Vec<(Method, String, Arc<Limiter<IpAddr>>)>
+}
+impl RateLimit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Limit `method` requests to `path`, in every version of the API
+    pub fn route(mut self, method: Method, path: &str, limiter: Limiter<IpAddr>) -> Self {
+        self.routes.push((method, path.to_string(), Arc::new(limiter)));
+        self
+    }
+    /// Forget idle clients of every route
+    pub fn prune(&self) {
+        for (_, _, limiter) in &self.routes {
+            limiter.prune();
+        }
+    }
+}
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<std::result::Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service,
+            routes: self.routes.clone()
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: S,
+    routes: Vec<(Method, String, Arc<Limiter<IpAddr>>)>
+}
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = std::result::Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let ip = super::proxy::client_ip(req.request());
+        let request_path = super::unversioned(req.path());
+        let limited = self.routes.iter()
+            .filter(|(method, path, _)| method == req.method() && path == request_path)
+            .find_map(|(_, _, limiter)| limiter.check(ip?).err());
+        match limited {
+            Some(retry) => Box::pin(ready(Err(Error::RateLimited(retry).into()))),
+            None => Box::pin(self.service.call(req))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::ResponseError;
+
+    fn seconds(duration: std::result::Result<(), Duration>) -> f64 {
+        duration.unwrap_err().as_secs_f64()
+    }
+
+    #[test]
+    fn buckets_refill_at_the_rate() {
+        let limiter = Limiter::new(60, 3);
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.check_at("a", start).is_ok());
+        }
+        assert!((seconds(limiter.check_at("a", start)) - 1.0).abs() < 1e-9);
+        let later = start + Duration::from_millis(500);
+        assert!((seconds(limiter.check_at("a", later)) - 0.5).abs() < 1e-9);
+        assert!(limiter.check_at("a", start + Duration::from_secs(1)).is_ok());
+        // Other keys have their own buckets
+        assert!(limiter.check_at("b", start).is_ok());
+        // Buckets never hold more than the burst
+        let idle = start + Duration::from_secs(3600);
+        for _ in 0..3 {
+            assert!(limiter.check_at("a", idle).is_ok());
+        }
+        assert!(limiter.check_at("a", idle).is_err());
+    }
+
+    #[test]
+    fn zero_rate_is_unlimited() {
+        let limiter = Limiter::new(0, 1);
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert!(limiter.check_at("a", now).is_ok());
+        }
+    }
+
+    #[test]
+    fn full_limiter_forgets_the_oldest_buckets() {
+        let limiter = Limiter::new(1, 1).with_capacity(4);
+        let start = Instant::now();
+        for key in 0..4 {
+            assert!(limiter.check_at(key, start + Duration::from_secs(key)).is_ok());
+        }
+        assert!(limiter.check_at(4, start + Duration::from_secs(4)).is_ok());
+        assert!(limiter.buckets.lock().unwrap().len() <= 4);
+        // The oldest was forgotten, so may burst again, while the newest is still limited
+        assert!(limiter.check_at(0, start + Duration::from_secs(5)).is_ok());
+        assert!(limiter.check_at(3, start + Duration::from_secs(5)).is_err());
+        for key in 5..1000 {
+            limiter.check_at(key, start + Duration::from_secs(key)).unwrap();
+            assert!(limiter.buckets.lock().unwrap().len() <= 4);
+        }
+    }
+
+    #[test]
+    fn retry_after_rounds_up() {
+        let retry_after = |retry: Duration| Error::RateLimited(retry)
+            .error_response()
+            .headers()
+            .get(actix_web::http::header::RETRY_AFTER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(retry_after(Duration::from_secs(2)), "2");
+        assert_eq!(retry_after(Duration::from_millis(1001)), "2");
+        assert_eq!(retry_after(Duration::from_nanos(1)), "1");
+        assert_eq!(retry_after(Duration::from_secs_f64(0.5)), "1");
+    }
+}
@@ -0,0 +1,82 @@
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    time::Instant
+};
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use tracing::Instrument;
+
+/// Middleware logging every request with its method, path, client address, status, latency and authenticated user,
+/// and recording the latency in the metrics of the route.
+///
+/// Handlers run within the request span, so anything they log is attributed to the request.
+/// Authenticating a user records them in the `user` field of the span.
+#[derive(Clone, Default)]
+pub struct RequestLog;
+impl<S, B> Transform<S, ServiceRequest> for RequestLog
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = RequestLogMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestLogMiddleware { service }))
+    }
+}
+
+pub struct RequestLogMiddleware<S> {
+    service: S
+}
+impl<S, B> Service<ServiceRequest> for RequestLogMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let client = super::proxy::client_ip(req.request())
+            .map(|ip| ip.to_string())
+            .unwrap_or_default();
+        let span = tracing::info_span!(
+            "request",
+            method = %req.method(),
+            path = %req.path(),
+            client = %client,
+            scheme = %super::proxy::scheme(req.request()),
+            user = tracing::field::Empty
+        );
+        let method = req.method().to_string();
+        let route = req.match_pattern();
+        let start = Instant::now();
+        let response = span.in_scope(|| self.service.call(req));
+        Box::pin(async move {
+            let response = response.await;
+            let latency = start.elapsed();
+            let latency_ms = latency.as_secs_f64() * 1000.0;
+            let (status, error) = match &response {
+                Ok(response) => (response.status(), response.response().error()),
+                Err(error) => (error.as_response_error().status_code(), Some(error))
+            };
+            crate::metrics::request(&method, route.as_deref().unwrap_or("unmatched"), status.as_u16(), latency);
+            match error {
+                Some(error) if status.is_server_error() => tracing::error!(status = status.as_u16(), latency_ms, %error, "request failed"),
+                Some(error) => tracing::info!(status = status.as_u16(), latency_ms, %error, "request rejected"),
+                None => tracing::info!(status = status.as_u16(), latency_ms, "request")
+            }
+            response
+        }.instrument(span))
+    }
+}
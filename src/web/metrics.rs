@@ -0,0 +1,15 @@
+use super::prelude::*;
+
+/// Metrics in the Prometheus text format
+#[utoipa::path(responses(
+    (status = 200, description = "The metrics", body = String, content_type = "text/plain")
+))]
+#[get("/metrics")]
+async fn metrics(db: Data<Pool>) -> Result<impl Responder> {
+    let size = with_db(&db, data::DictionarySize::get)
+        .await?;
+    Ok(HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(crate::metrics::encode(&db, &size)?)
+    )
+}
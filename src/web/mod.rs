@@ -1,22 +1,147 @@
+use std::time::{Duration, Instant};
+
+use actix_web::{dev::HttpServiceFactory, http::header, middleware::DefaultHeaders};
+
 use prelude::*;
 mod prelude {
     pub use crate::prelude::*;
     pub use actix_web::{
-        App,
         get,
         http::header::ContentType,
+        HttpRequest,
         HttpResponse,
-        HttpServer,
         post,
+        put,
         Responder,
         web::{
             self,
             Data,
             Json,
-            Path
+            Path,
+            Query
         }
     };
+    pub use serde::{Serialize, Deserialize};
+    pub use utoipa::{IntoParams, ToSchema};
+    pub use super::with_db;
+}
+
+/// Run work needing a database connection on the blocking thread pool.
+/// 
+/// Checking out a connection, SQLite queries and signature verification can all block,
+/// so they must be kept off of the async workers by doing them within `f`.
+/// 
+/// The work is traced within the span of the request, and logged as a warning if it is slow.
+pub async fn with_db<F, T>(db: &Data<Pool>, f: F) -> Result<T>
+where
+    F: 'static + Send + FnOnce(&data::Connection) -> Result<T>,
+    T: 'static + Send
+{
+    let db = db.clone();
+    let span = tracing::Span::current();
+    web::block(move || {
+        let _request = span.enter();
+        let start = Instant::now();
+        let result = f(&db.get()?);
+        let elapsed = start.elapsed();
+        if elapsed >= slow_query_threshold() {
+            tracing::warn!(elapsed_ms = elapsed.as_secs_f64() * 1000.0, "slow database call");
+        }
+        result
+    }).await?
+}
+
+/// Where routes are registered: the server itself, or the OpenAPI document describing it.
+/// 
+/// Routes are only ever registered through this trait, and the document is built by registering the same
+/// routes as the server, so every route is documented.
+pub trait Routes {
+    /// Register a handler, documented by a `#[utoipa::path]` attribute above its route attribute
+    fn route<S>(&mut self, service: S) -> &mut Self
+    where
+        S: HttpServiceFactory + utoipa::Path + utoipa::__dev::SchemaReferences + 'static;
+    /// Register the routes of a version of the API under its prefix, such as `/v1`
+    fn version(&mut self, prefix: &'static str, routes: fn(&mut Self)) -> &mut Self;
+    /// Also register the routes of a version at the root, as deprecated aliases of those under `successor`
+    fn deprecated_alias(&mut self, successor: &'static str, routes: fn(&mut Self)) -> &mut Self;
+}
+impl Routes for web::ServiceConfig {
+    fn route<S>(&mut self, service: S) -> &mut Self
+    where
+        S: HttpServiceFactory + utoipa::Path + utoipa::__dev::SchemaReferences + 'static
+    {
+        self.service(service)
+    }
+    fn version(&mut self, prefix: &'static str, routes: fn(&mut Self)) -> &mut Self {
+        self.service(web::scope(prefix).configure(routes))
+    }
+    fn deprecated_alias(&mut self, successor: &'static str, routes: fn(&mut Self)) -> &mut Self {
+        self.service(web::scope("")
+            .wrap(DefaultHeaders::new()
+                .add(("Deprecation", "true"))
+                .add((header::LINK, format!("<{}>; rel=\"successor-version\"", successor)))
+            )
+            .configure(routes)
+        )
+    }
+}
+
+/// Mount every version of the API.
+///
+/// Each version is a scope under its own prefix. The first version was served at the root, so its routes are
+/// also kept there as deprecated aliases. A new version must only be added, never change the routes of an
+/// older one, so that deployed clients keep working.
+pub fn api<R: Routes>(routes: &mut R) {
+    routes.version("/v1", v1)
+        // Matches every path, so must be mounted after all other services
+        .deprecated_alias("/v1", v1);
+}
+
+/// Routes of the first version of the API
+fn v1<R: Routes>(routes: &mut R) {
+    routes.route(auth::register)
+        .route(auth::challenge)
+        .route(auth::login)
+        .route(auth::logout)
+        .route(auth::sessions)
+
+        .route(dictionary::list_words)
+        .route(dictionary::get_word)
+        // Must come before `set_word` so that `batch` is not taken as a word
+        .route(dictionary::get_words)
+        .route(dictionary::set_word)
+        .route(dictionary::update_word)
+        .route(dictionary::get_kanji)
+        .route(dictionary::get_kanji_batch);
+}
+
+/// The path without any leading version prefix, such as `/v1`, so that versions can share per-route state
+pub fn unversioned(path: &str) -> &str {
+    let rest = match path.strip_prefix("/v") {
+        Some(rest) => rest,
+        None => return path
+    };
+    let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    match &rest[digits..] {
+        unversioned if digits > 0 && unversioned.starts_with('/') => unversioned,
+        _ => path
+    }
+}
+
+fn slow_query_threshold() -> Duration {
+    Duration::from_millis(crate::CONFIG.get().map(|config| config.slow_query_threshold).unwrap_or(u64::MAX))
 }
 
 pub mod auth;
-pub mod dictionary;
\ No newline at end of file
+pub mod caching;
+pub mod compress;
+pub mod cors;
+pub mod dictionary;
+pub mod format;
+pub mod health;
+pub mod limit;
+pub mod log;
+pub mod metrics;
+pub mod openapi;
+pub mod proxy;
+pub mod stats;
\ No newline at end of file
@@ -0,0 +1,196 @@
+use super::prelude::*;
+
+use std::marker::PhantomData;
+
+use once_cell::sync::Lazy;
+use utoipa::{
+    openapi::{
+        path::Paths,
+        schema::{OneOfBuilder, Schema},
+        security::{Http, HttpAuthScheme, SecurityScheme},
+        ComponentsBuilder,
+        InfoBuilder,
+        OpenApi,
+        OpenApiBuilder,
+        RefOr
+    },
+    __dev::ComposeSchema
+};
+
+/// A body that is either signed by the user, or sent alone with a session token in the `Authorization` header.
+/// Only used to describe such bodies, which are read by `auth::authorise`.
+pub struct Authorised<T>(PhantomData<T>);
+impl<T: ToSchema> ComposeSchema for Authorised<T> {
+    fn compose(generics: Vec<RefOr<Schema>>) -> RefOr<Schema> {
+        let data = generics.into_iter().next().unwrap_or_else(T::schema);
+        let by = data::By::<T>::compose(vec![data.clone()]);
+        OneOfBuilder::new()
+            .item(data::Signed::<data::By<T>>::compose(vec![by]))
+            .item(data)
+            .into()
+    }
+}
+impl<T: ToSchema> ToSchema for Authorised<T> {
+    fn schemas(schemas: &mut Vec<(String, RefOr<Schema>)>) {
+        T::schemas(schemas);
+    }
+}
+
+/// Describes the routes registered with it
+#[derive(Default)]
+struct Document {
+    /// The prefix of the version whose routes are being registered
+    prefix: String,
+    paths: Paths,
+    schemas: Vec<(String, RefOr<Schema>)>
+}
+impl super::Routes for Document {
+    fn route<S>(&mut self, _: S) -> &mut Self
+    where
+        S: actix_web::dev::HttpServiceFactory + utoipa::Path + utoipa::__dev::SchemaReferences + 'static
+    {
+        self.paths.add_path_operation(format!("{}{}", self.prefix, S::path()), S::methods(), S::operation());
+        S::schemas(&mut self.schemas);
+        self
+    }
+    fn version(&mut self, prefix: &'static str, routes: fn(&mut Self)) -> &mut Self {
+        let inner = format!("{}{}", self.prefix, prefix);
+        let outer = std::mem::replace(&mut self.prefix, inner);
+        routes(self);
+        self.prefix = outer;
+        self
+    }
+    /// Aliases are explained once in the description, rather than documenting every route twice
+    fn deprecated_alias(&mut self, _: &'static str, _: fn(&mut Self)) -> &mut Self {
+        self
+    }
+}
+
+/// The OpenAPI document describing every route, built by registering the routes of the server
+pub fn document() -> OpenApi {
+    let mut routes = Document::default();
+    crate::routes(&mut routes, true);
+    OpenApiBuilder::new()
+        .info(InfoBuilder::new()
+            .title("お単語")
+            .description(Some("Routes of version 1 are also served without the /v1 prefix, as deprecated aliases."))
+            .version(env!("CARGO_PKG_VERSION"))
+        )
+        .paths(routes.paths)
+        .components(Some(ComponentsBuilder::new()
+            .schemas_from_iter(routes.schemas)
+            .security_scheme("session", SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)))
+            .build()
+        ))
+        .build()
+}
+
+static DOCUMENT: Lazy<String> = Lazy::new(|| document().to_json().expect("the document is always serialisable"));
+
+/// This document
+#[utoipa::path(responses(
+    (status = 200, description = "The OpenAPI document", body = serde_json::Value)
+))]
+#[get("/openapi.json")]
+async fn openapi() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type(ContentType::json())
+        .body(DOCUMENT.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{dev::Service, http::{header::HeaderValue, Method, StatusCode}, test, App};
+
+    /// The documented path with each parameter filled in
+    fn example(path: &str) -> String {
+        path.replace("{word}", "x")
+            .replace("{kanji}", "%E5%AD%97")
+    }
+
+    /// Every schema referenced within `value`
+    fn references<'a>(value: &'a serde_json::Value, found: &mut Vec<&'a str>) {
+        match value {
+            serde_json::Value::Object(object) => {
+                if let Some(reference) = object.get("$ref").and_then(|reference| reference.as_str()) {
+                    found.push(reference);
+                }
+                object.values().for_each(|value| references(value, found));
+            },
+            serde_json::Value::Array(array) => array.iter().for_each(|value| references(value, found)),
+            _ => ()
+        }
+    }
+
+    /// Counts the operations registered with it
+    #[derive(Default)]
+    struct Count(usize);
+    impl super::super::Routes for Count {
+        fn route<S>(&mut self, _: S) -> &mut Self
+        where
+            S: actix_web::dev::HttpServiceFactory + utoipa::Path + utoipa::__dev::SchemaReferences + 'static
+        {
+            self.0 += S::methods().len();
+            self
+        }
+        fn version(&mut self, _: &'static str, routes: fn(&mut Self)) -> &mut Self {
+            routes(self);
+            self
+        }
+        fn deprecated_alias(&mut self, _: &'static str, _: fn(&mut Self)) -> &mut Self {
+            self
+        }
+    }
+
+    #[actix_web::test]
+    async fn document_matches_the_routes() {
+        // Report the pattern of the route that handled each request
+        let app = test::init_service(App::new()
+            .wrap_fn(|req, service| {
+                let response = service.call(req);
+                async {
+                    let mut response = response.await?;
+                    if let Some(pattern) = response.request().match_pattern() {
+                        let pattern = HeaderValue::from_str(&pattern).unwrap();
+                        response.headers_mut().insert("x-pattern".parse().unwrap(), pattern);
+                    }
+                    Ok(response)
+                }
+            })
+            .configure(|cfg| crate::routes(cfg, true))
+            .default_service(web::to(HttpResponse::ImATeapot))
+        ).await;
+
+        let document = serde_json::to_value(document()).unwrap();
+        let paths = document["paths"].as_object().unwrap();
+        let mut documented_operations = 0;
+        for method in [Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE] {
+            for (path, operations) in paths {
+                let documented = operations.get(method.as_str().to_lowercase()).is_some();
+                let request = test::TestRequest::default()
+                    .method(method.clone())
+                    .uri(&example(path))
+                    .to_request();
+                let response = test::call_service(&app, request).await;
+                let routed = response.status() != StatusCode::IM_A_TEAPOT
+                    && response.headers().get("x-pattern").is_some_and(|pattern| pattern == path.as_str());
+                assert_eq!(routed, documented, "{} {} is routed: {}, documented: {}", method, path, routed, documented);
+                documented_operations += documented as usize;
+            }
+        }
+
+        // Every registered route is documented, without any replacing another at the same method and path
+        let mut registered = Count::default();
+        crate::routes(&mut registered, true);
+        assert_eq!(registered.0, documented_operations);
+        assert!(paths["/"].get("get").is_some());
+
+        let mut found = Vec::new();
+        references(&document, &mut found);
+        for reference in found {
+            let name = reference.strip_prefix("#/components/schemas/").unwrap();
+            assert!(document["components"]["schemas"].get(name).is_some(), "{} is not in the document", reference);
+        }
+    }
+}
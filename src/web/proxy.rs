@@ -0,0 +1,120 @@
+use super::prelude::*;
+
+use std::net::IpAddr;
+
+use actix_web::http::{header, uri::Authority};
+use once_cell::sync::Lazy;
+
+/// Proxies whose forwarding headers are believed, from the configuration
+static TRUSTED: Lazy<Vec<IpAddr>> = Lazy::new(|| crate::CONFIG.get()
+    .map(|config| config.trusted_proxies.iter()
+        .filter_map(|proxy| proxy.parse().ok())
+        .collect())
+    .unwrap_or_default()
+);
+
+fn trusted(ip: &IpAddr) -> bool {
+    TRUSTED.contains(ip)
+}
+
+/// The IP address of the client that made a request.
+///
+/// When the peer is a trusted proxy, this is the last address in `X-Forwarded-For` that is not
+/// itself a trusted proxy, as anything before it could have been written by the client.
+pub fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    let peer = req.peer_addr()?.ip();
+    if !trusted(&peer) {
+        return Some(peer)
+    }
+    let forwarded = req.headers().get_all("X-Forwarded-For")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|ip| ip.trim().parse::<IpAddr>())
+        .collect::<Vec<_>>();
+    let mut client = peer;
+    for ip in forwarded.into_iter().rev() {
+        match ip {
+            Ok(ip) if trusted(&ip) => client = ip,
+            Ok(ip) => return Some(ip),
+            Err(_) => break
+        }
+    }
+    Some(client)
+}
+
+/// The scheme the client used to make a request, from `X-Forwarded-Proto` if the peer is a trusted proxy
+pub fn scheme(req: &HttpRequest) -> String {
+    let forwarded = req.peer_addr()
+        .filter(|peer| trusted(&peer.ip()))
+        .and_then(|_| req.headers().get("X-Forwarded-Proto"))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .map(|scheme| scheme.trim().to_ascii_lowercase());
+    forwarded.unwrap_or_else(|| {
+        let tls = crate::CONFIG.get().is_none_or(|config| config.tls);
+        if tls { "https" } else { "http" }.to_string()
+    })
+}
+
+/// The host the client requested, from `X-Forwarded-Host` if the peer is a trusted proxy.
+///
+/// `ConnectionInfo::host` is not used as it believes forwarding headers from any peer.
+pub fn host(req: &HttpRequest) -> Option<String> {
+    let forwarded = req.peer_addr()
+        .filter(|peer| trusted(&peer.ip()))
+        .and_then(|_| req.headers().get("X-Forwarded-Host"))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next());
+    forwarded
+        .or_else(|| req.headers().get(header::HOST).and_then(|value| value.to_str().ok()))
+        .or_else(|| req.uri().authority().map(Authority::as_str))
+        .map(|host| host.trim().to_string())
+}
+
+/// Permanently redirect any request to the same path over HTTPS
+pub async fn to_https(req: HttpRequest) -> HttpResponse {
+    let host = match host(&req).and_then(|host| host.parse::<Authority>().ok()) {
+        Some(authority) => authority.host().to_string(),
+        None => return HttpResponse::BadRequest().body("The request has no valid Host header")
+    };
+    let port = crate::CONFIG.get()
+        .map_or(443, |config| config.address.port());
+    let authority = if port == 443 {
+        host
+    } else {
+        format!("{}:{}", host, port)
+    };
+    let path = req.uri().path_and_query()
+        .map_or("/", |path| path.as_str());
+    HttpResponse::PermanentRedirect()
+        .insert_header((header::LOCATION, format!("https://{}{}", authority, path)))
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[actix_web::test]
+    async fn redirect_ignores_untrusted_forwarded_host() {
+        let req = TestRequest::get()
+            .uri("/v1/word?limit=5")
+            .peer_addr("203.0.113.1:1234".parse().unwrap())
+            .insert_header((header::HOST, "example.com:80"))
+            .insert_header(("X-Forwarded-Host", "attacker.example"))
+            .to_http_request();
+        let response = to_https(req).await;
+        assert_eq!(response.status(), actix_web::http::StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers().get(header::LOCATION).unwrap(), "https://example.com/v1/word?limit=5");
+    }
+
+    #[actix_web::test]
+    async fn redirect_requires_a_host() {
+        let req = TestRequest::get()
+            .uri("/")
+            .insert_header((header::HOST, "not a host"))
+            .to_http_request();
+        assert_eq!(to_https(req).await.status(), actix_web::http::StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,23 @@
+use super::prelude::*;
+
+#[derive(Serialize, ToSchema)]
+struct Stats {
+    cache: cache::DictionaryStats,
+    pruned: crate::prune::PrunedStats
+}
+
+/// Cache and pruning statistics
+#[utoipa::path(responses(
+    (status = 200, description = "The statistics", body = Stats)
+))]
+#[get("/stats")]
+async fn stats(cache: Data<cache::Dictionary>, pruned: Data<crate::prune::Pruned>) -> Result<impl Responder> {
+    let stats = Stats {
+        cache: cache.stats(),
+        pruned: pruned.stats()
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::json())
+        .body(serde_json::to_string(&stats)?)
+    )
+}